@@ -0,0 +1,399 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use alloy::{
+    eips::eip7702::Authorization,
+    primitives::{Address, B256, Signature, keccak256},
+    rpc::types::TransactionRequest,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::prelude::*;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    Signed,
+    /// A cached signature returned for a retried request.
+    Replayed,
+    Rejected,
+    Failed,
+    /// Marks where the service resumed a log whose chain was broken.
+    ChainBroken,
+}
+
+impl Decision {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Decision::Signed => "signed",
+            Decision::Replayed => "replayed",
+            Decision::Rejected => "rejected",
+            Decision::Failed => "failed",
+            Decision::ChainBroken => "chain_broken",
+        }
+    }
+}
+
+/// What a record's signature covers.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordKind {
+    /// A transaction. Left out of the record, so records written before
+    /// other kinds existed keep their hashes.
+    #[default]
+    Transaction,
+    /// An EIP-7702 authorization, signed as `keccak256(0x05 || rlp(authorization))`.
+    Authorization,
+}
+
+impl RecordKind {
+    fn is_transaction(&self) -> bool {
+        *self == RecordKind::Transaction
+    }
+}
+
+/// A signing decision as seen by the request handler, before it is chained.
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub caller: String,
+    pub method: String,
+    pub backend: &'static str,
+    pub kind: RecordKind,
+    pub summary: Value,
+    pub decision: Decision,
+    pub reason: Option<String>,
+    pub tx_hash: Option<B256>,
+    pub signature: Option<Signature>,
+}
+
+/// The hashed part of an audit record. `prev_hash` links it to the record
+/// before it, so editing or removing any line breaks the chain after it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditEntry {
+    pub seq: u64,
+    /// Unix timestamp in milliseconds.
+    pub timestamp: u64,
+    pub caller: String,
+    pub method: String,
+    pub backend: String,
+    #[serde(default, skip_serializing_if = "RecordKind::is_transaction")]
+    pub kind: RecordKind,
+    pub summary: Value,
+    pub decision: Decision,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<B256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<Signature>,
+    pub prev_hash: B256,
+}
+
+impl AuditEntry {
+    fn hash(&self) -> Result<B256> {
+        Ok(keccak256(serde_json::to_vec(self)?))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditRecord {
+    #[serde(flatten)]
+    pub entry: AuditEntry,
+    pub hash: B256,
+}
+
+struct ChainHead {
+    file: File,
+    next_seq: u64,
+    last_hash: B256,
+}
+
+/// Append-only JSON lines file holding one [`AuditRecord`] per line.
+pub struct AuditLog {
+    head: Mutex<ChainHead>,
+}
+
+impl AuditLog {
+    /// Open the log at `path` and verify its chain. A broken chain refuses to
+    /// open unless `allow_broken_chain` is set, in which case a
+    /// [`Decision::ChainBroken`] record is appended after the last readable
+    /// record and the chain continues from there.
+    pub fn open(path: impl AsRef<Path>, allow_broken_chain: bool) -> Result<Self> {
+        let path = path.as_ref();
+        let mut broken_at = None;
+        let (next_seq, last_hash) = match File::open(path) {
+            Ok(file) => match verify_reader(BufReader::new(file)) {
+                Ok(head) => head,
+                Err(Error::AuditChainBroken(seq)) if allow_broken_chain => {
+                    tracing::warn!(
+                        seq,
+                        "audit chain is broken, appending after the last record"
+                    );
+                    broken_at = Some(seq);
+                    last_record(path)?
+                        .map(|r| (r.entry.seq + 1, r.hash))
+                        .unwrap_or((0, B256::ZERO))
+                }
+                Err(e) => return Err(e),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (0, B256::ZERO),
+            Err(e) => return Err(e.into()),
+        };
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        let log = Self {
+            head: Mutex::new(ChainHead {
+                file,
+                next_seq,
+                last_hash,
+            }),
+        };
+        if let Some(seq) = broken_at {
+            log.append(AuditEvent {
+                caller: "eth-signer".to_string(),
+                method: "audit_open".to_string(),
+                backend: "none",
+                kind: RecordKind::default(),
+                summary: json!({ "broken_at": seq }),
+                decision: Decision::ChainBroken,
+                reason: Some(format!("audit chain broken at record {seq}")),
+                tx_hash: None,
+                signature: None,
+            })?;
+        }
+        Ok(log)
+    }
+
+    pub fn append(&self, event: AuditEvent) -> Result<AuditRecord> {
+        let mut head = self.head.lock().expect("audit log lock poisoned");
+
+        let entry = AuditEntry {
+            seq: head.next_seq,
+            timestamp: now_millis(),
+            caller: event.caller,
+            method: event.method,
+            backend: event.backend.to_string(),
+            kind: event.kind,
+            summary: event.summary,
+            decision: event.decision,
+            reason: event.reason,
+            tx_hash: event.tx_hash,
+            signature: event.signature,
+            prev_hash: head.last_hash,
+        };
+        let hash = entry.hash()?;
+        let record = AuditRecord { entry, hash };
+
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        head.file.write_all(&line)?;
+        head.file.sync_data()?;
+
+        head.next_seq += 1;
+        head.last_hash = hash;
+        Ok(record)
+    }
+}
+
+/// Summary of a transaction request suitable for the audit trail. Calldata is
+/// recorded by length and hash only, and blobs by their versioned hashes.
+pub fn tx_summary(request: &TransactionRequest) -> Value {
+    let input = request.input.input();
+    json!({
+        "from": request.from,
+        "to": request.to,
+        "value": request.value,
+        "nonce": request.nonce,
+        "chain_id": request.chain_id,
+        "gas": request.gas,
+        "gas_price": request.gas_price,
+        "max_fee_per_gas": request.max_fee_per_gas,
+        "max_priority_fee_per_gas": request.max_priority_fee_per_gas,
+        "input_len": input.map(|i| i.len()).unwrap_or_default(),
+        "input_hash": input.map(keccak256),
+        "max_fee_per_blob_gas": request.max_fee_per_blob_gas,
+        "blob_versioned_hashes": request.blob_versioned_hashes,
+        "authorization_delegates": request
+            .authorization_list
+            .as_ref()
+            .map(|list| list.iter().map(|a| a.address).collect::<Vec<_>>()),
+    })
+}
+
+/// Summary of an EIP-7702 authorization for the audit trail.
+pub fn authorization_summary(from: Address, authorization: &Authorization) -> Value {
+    json!({
+        "from": from,
+        "chain_id": authorization.chain_id,
+        "delegate": authorization.address,
+        "nonce": authorization.nonce,
+        "signature_hash": authorization.signature_hash(),
+    })
+}
+
+/// Verify the hash chain of the audit log at `path`, returning the number of
+/// records checked.
+pub fn verify(path: impl AsRef<Path>) -> Result<u64> {
+    let (count, _) = verify_reader(BufReader::new(File::open(path)?))?;
+    Ok(count)
+}
+
+fn verify_reader(reader: impl BufRead) -> Result<(u64, B256)> {
+    let mut expected_seq = 0;
+    let mut prev_hash = B256::ZERO;
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: AuditRecord =
+            serde_json::from_str(&line).map_err(|_| Error::AuditChainBroken(expected_seq))?;
+        if record.entry.seq != expected_seq
+            || record.entry.prev_hash != prev_hash
+            || record.entry.hash()? != record.hash
+        {
+            return Err(Error::AuditChainBroken(expected_seq));
+        }
+        expected_seq += 1;
+        prev_hash = record.hash;
+    }
+    Ok((expected_seq, prev_hash))
+}
+
+fn last_record(path: &Path) -> Result<Option<AuditRecord>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut last = None;
+    for line in reader.lines() {
+        if let Ok(record) = serde_json::from_str::<AuditRecord>(&line?) {
+            last = Some(record);
+        }
+    }
+    Ok(last)
+}
+
+/// Write the records with `from <= seq <= to` to `out`, one per line.
+pub fn export(
+    path: impl AsRef<Path>,
+    from: Option<u64>,
+    to: Option<u64>,
+    mut out: impl Write,
+) -> Result<u64> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut exported = 0;
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: AuditRecord = serde_json::from_str(&line)?;
+        let seq = record.entry.seq;
+        if from.is_some_and(|from| seq < from) {
+            continue;
+        }
+        if to.is_some_and(|to| seq > to) {
+            break;
+        }
+        writeln!(out, "{line}")?;
+        exported += 1;
+    }
+    Ok(exported)
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(decision: Decision) -> AuditEvent {
+        AuditEvent {
+            caller: "127.0.0.1:1234".to_string(),
+            method: "eth_signTransaction".to_string(),
+            backend: "private_key",
+            kind: RecordKind::Transaction,
+            summary: json!({ "nonce": "0x1" }),
+            decision,
+            reason: None,
+            tx_hash: None,
+            signature: None,
+        }
+    }
+
+    #[test]
+    fn chain_detects_tampering() {
+        let path =
+            std::env::temp_dir().join(format!("eth-signer-audit-{}.log", std::process::id()));
+
+        let log = AuditLog::open(&path, false).unwrap();
+        log.append(event(Decision::Signed)).unwrap();
+        log.append(event(Decision::Rejected)).unwrap();
+        drop(log);
+
+        // Reopening continues the chain where it left off.
+        let log = AuditLog::open(&path, false).unwrap();
+        assert_eq!(log.append(event(Decision::Failed)).unwrap().entry.seq, 2);
+        drop(log);
+        assert_eq!(verify(&path).unwrap(), 3);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, content.replacen("rejected", "signed", 1)).unwrap();
+        assert!(matches!(verify(&path), Err(Error::AuditChainBroken(1))));
+
+        // A broken chain is only resumed on request, and the break is recorded.
+        assert!(matches!(
+            AuditLog::open(&path, false),
+            Err(Error::AuditChainBroken(1))
+        ));
+        let log = AuditLog::open(&path, true).unwrap();
+        assert_eq!(log.append(event(Decision::Signed)).unwrap().entry.seq, 4);
+        drop(log);
+        let marker: AuditRecord = serde_json::from_str(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .lines()
+                .nth(3)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(marker.entry.decision, Decision::ChainBroken);
+        assert_eq!(marker.entry.summary["broken_at"], 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn record_authorizations_as_their_own_kind() {
+        let path =
+            std::env::temp_dir().join(format!("eth-signer-audit-kind-{}.log", std::process::id()));
+        let log = AuditLog::open(&path, false).unwrap();
+        log.append(event(Decision::Signed)).unwrap();
+        log.append(AuditEvent {
+            kind: RecordKind::Authorization,
+            ..event(Decision::Signed)
+        })
+        .unwrap();
+        drop(log);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        // Transaction records are written as before kinds existed.
+        assert!(!lines[0].contains("\"kind\""));
+        let record: AuditRecord = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(record.entry.kind, RecordKind::Authorization);
+        assert_eq!(verify(&path).unwrap(), 2);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+use alloy::{
+    consensus::{Signed, TxEip4844Variant, TxEnvelope},
+    eips::{
+        eip4844::env_settings::EnvKzgSettings,
+        eip7594::{BlobTransactionSidecarVariant, MAX_BLOBS_PER_TX_FUSAKA},
+        eip7691::MAX_BLOBS_PER_BLOCK_ELECTRA,
+    },
+    primitives::{B256, TxKind},
+    rpc::types::TransactionRequest,
+};
+
+use crate::prelude::*;
+
+/// Check the blob sidecar of an EIP-4844 request before it is signed, and
+/// fill in `blobVersionedHashes` from it when the caller left them out.
+///
+/// The signature only covers the versioned hashes, so a sidecar whose
+/// commitments or proofs do not match them would produce a transaction that
+/// nodes drop. Every blob is verified against its commitment and proof, and
+/// the hashes the caller sent must be the hashes of those commitments, in
+/// order.
+pub fn check_sidecar(request: &mut TransactionRequest) -> Result<()> {
+    if !request.has_eip4844_blob_data() {
+        return Ok(());
+    }
+    let Some(sidecar) = &request.sidecar else {
+        return Err(Error::InvalidBlob(
+            "a sidecar is required to sign a blob transaction".to_string(),
+        ));
+    };
+    if !matches!(request.to, Some(TxKind::Call(_))) {
+        return Err(Error::InvalidBlob(
+            "a blob transaction cannot create a contract".to_string(),
+        ));
+    }
+
+    let (count, max) = match sidecar {
+        BlobTransactionSidecarVariant::Eip4844(sidecar) => {
+            (sidecar.blobs.len(), MAX_BLOBS_PER_BLOCK_ELECTRA)
+        }
+        BlobTransactionSidecarVariant::Eip7594(sidecar) => {
+            (sidecar.blobs.len(), MAX_BLOBS_PER_TX_FUSAKA)
+        }
+    };
+    if count == 0 || count as u64 > max {
+        return Err(Error::InvalidBlob(format!(
+            "{count} blobs, expected 1 to {max}"
+        )));
+    }
+
+    let hashes: Vec<B256> = sidecar.versioned_hashes().collect();
+    if let Some(requested) = &request.blob_versioned_hashes
+        && *requested != hashes
+    {
+        return Err(Error::InvalidBlob(
+            "blobVersionedHashes do not match the sidecar commitments".to_string(),
+        ));
+    }
+    sidecar
+        .validate(&hashes, EnvKzgSettings::Default.get())
+        .map_err(|e| Error::InvalidBlob(e.to_string()))?;
+
+    request.blob_versioned_hashes = Some(hashes);
+    Ok(())
+}
+
+/// The envelope without its blob sidecar. The transaction hash and
+/// signature do not cover the sidecar, so they are unchanged.
+pub fn strip_sidecar(envelope: TxEnvelope) -> TxEnvelope {
+    match envelope {
+        TxEnvelope::Eip4844(signed) => {
+            let (tx, signature, hash) = signed.into_parts();
+            TxEnvelope::Eip4844(Signed::new_unchecked(tx.drop_sidecar(), signature, hash))
+        }
+        envelope => envelope,
+    }
+}
+
+/// Attach `sidecar` again to a blob transaction signed without it.
+pub fn attach_sidecar(
+    envelope: TxEnvelope,
+    sidecar: Option<BlobTransactionSidecarVariant>,
+) -> TxEnvelope {
+    match (envelope, sidecar) {
+        (TxEnvelope::Eip4844(signed), Some(sidecar)) => {
+            let (tx, signature, hash) = signed.into_parts();
+            let tx = match tx {
+                TxEip4844Variant::TxEip4844(tx) => tx.with_sidecar(sidecar).into(),
+                tx => tx,
+            };
+            TxEnvelope::Eip4844(Signed::new_unchecked(tx, signature, hash))
+        }
+        (envelope, _) => envelope,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::{
+        consensus::{SidecarBuilder, SimpleCoder},
+        eips::eip2718::{Decodable2718, Encodable2718},
+        network::{EthereumWallet, TransactionBuilder, TransactionBuilder4844},
+        primitives::{Address, Bytes},
+        signers::local::PrivateKeySigner,
+    };
+
+    fn request() -> TransactionRequest {
+        let sidecar = SidecarBuilder::<SimpleCoder>::from_slice(b"batch")
+            .build()
+            .unwrap();
+        TransactionRequest::default()
+            .to(Address::repeat_byte(2))
+            .with_blob_sidecar(sidecar)
+    }
+
+    #[test]
+    fn check_blob_sidecars() {
+        let mut request = request();
+        let hashes = request.blob_versioned_hashes.take();
+        check_sidecar(&mut request).unwrap();
+        assert_eq!(request.blob_versioned_hashes, hashes);
+
+        let mut wrong_hash = request.clone();
+        wrong_hash.blob_versioned_hashes = Some(vec![B256::repeat_byte(1)]);
+        assert!(check_sidecar(&mut wrong_hash).is_err());
+
+        let mut wrong_proof = request.clone();
+        if let Some(BlobTransactionSidecarVariant::Eip4844(sidecar)) = &mut wrong_proof.sidecar {
+            sidecar.blobs[0][0] ^= 1;
+        }
+        let error = check_sidecar(&mut wrong_proof).unwrap_err().to_string();
+        assert!(error.contains("KZG"), "{error}");
+
+        let mut no_sidecar = request.clone();
+        no_sidecar.sidecar = None;
+        assert!(check_sidecar(&mut no_sidecar).is_err());
+
+        let mut plain = TransactionRequest::default().input(Bytes::from_static(b"call").into());
+        check_sidecar(&mut plain).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sign_with_sidecar() {
+        let signer = PrivateKeySigner::random();
+        let mut request = request()
+            .with_from(signer.address())
+            .with_nonce(0)
+            .with_chain_id(1)
+            .with_gas_limit(21_000)
+            .with_max_fee_per_gas(2)
+            .with_max_priority_fee_per_gas(1)
+            .with_max_fee_per_blob_gas(1);
+        check_sidecar(&mut request).unwrap();
+        let envelope = request
+            .clone()
+            .build(&EthereumWallet::new(signer))
+            .await
+            .unwrap();
+        crate::verify::check_signed(&request, &envelope).unwrap();
+
+        let encoded = envelope.encoded_2718();
+        let decoded = TxEnvelope::decode_2718(&mut encoded.as_slice()).unwrap();
+        let signed = decoded.as_eip4844().unwrap();
+        assert_eq!(signed.tx().sidecar(), request.sidecar.as_ref());
+        assert_eq!(decoded.tx_hash(), envelope.tx_hash());
+
+        let stripped = strip_sidecar(envelope.clone());
+        assert!(stripped.encode_2718_len() < encoded.len() / 100);
+        assert_eq!(stripped.tx_hash(), envelope.tx_hash());
+        assert_eq!(attach_sidecar(stripped, request.sidecar), envelope);
+    }
+}
@@ -0,0 +1,359 @@
+use std::{net::SocketAddr, path::Path};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::otel::TelemetryConfig;
+use crate::policy::Policy;
+use crate::prelude::*;
+use crate::redact::Redaction;
+use crate::secret::Secret;
+use crate::signer::{DerivationConfig, SignerConfig};
+
+/// Service configuration loaded with `--config`. String values may reference
+/// environment variables as `${NAME}` or `${NAME:-default}`; `$$` is a literal
+/// `$`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServiceConfig {
+    /// Addresses the JSON-RPC server listens on.
+    pub listeners: Vec<ListenerConfig>,
+    /// Signing keys. The first one answers requests without a `from`.
+    pub signers: Vec<SignerConfig>,
+    /// Per-request child keys, served by `hd_signTransaction` and `/derive`.
+    pub derivation: Option<DerivationConfig>,
+    /// Restrictions checked before a transaction is signed.
+    pub policy: Policy,
+    pub telemetry: TelemetryConfig,
+    pub log: LogConfig,
+    pub audit: AuditConfig,
+    pub idempotency: IdempotencyConfig,
+    pub secrets: SecretsConfig,
+    /// JSON-RPC node that receives the methods the signer does not serve.
+    pub upstream: Option<UpstreamConfig>,
+    pub admin: AdminConfig,
+    pub readiness: ReadinessConfig,
+}
+
+impl Default for ServiceConfig {
+    fn default() -> Self {
+        Self {
+            listeners: vec![ListenerConfig::default()],
+            signers: Vec::new(),
+            derivation: None,
+            policy: Policy::default(),
+            telemetry: TelemetryConfig::default(),
+            log: LogConfig::default(),
+            audit: AuditConfig::default(),
+            idempotency: IdempotencyConfig::default(),
+            secrets: SecretsConfig::default(),
+            upstream: None,
+            admin: AdminConfig::default(),
+            readiness: ReadinessConfig::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ListenerConfig {
+    pub address: SocketAddr,
+}
+
+impl Default for ListenerConfig {
+    fn default() -> Self {
+        Self {
+            address: SocketAddr::from(([0, 0, 0, 0], 8000)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// How calldata and raw transactions appear in logs and spans.
+    pub redact: Redaction,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditConfig {
+    /// Append-only audit log of every signing decision.
+    pub path: Option<String>,
+    /// Start even if the hash chain is broken. The break is recorded in the
+    /// log and the new records chain on from the last readable one.
+    pub allow_broken_chain: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdempotencyConfig {
+    /// Number of signed responses kept for idempotent retries, 0 disables.
+    pub capacity: usize,
+    /// Persist the cache so retries survive a restart.
+    pub path: Option<String>,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            path: None,
+        }
+    }
+}
+
+/// Secret fields accept `file:<path>` to read the value from a file, such as a
+/// Docker or Kubernetes secret mount.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecretsConfig {
+    /// Refuse secret files other users can read instead of warning.
+    pub strict_permissions: bool,
+    /// Lock signer secrets in memory with `mlock` so they are never swapped
+    /// out. Needs `CAP_IPC_LOCK` or a sufficient `ulimit -l`.
+    pub lock_memory: bool,
+    /// Disable core dumps, which would contain every secret in memory.
+    pub disable_core_dumps: bool,
+}
+
+/// Backend checks behind `/readyz`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReadinessConfig {
+    /// Seconds a key's probe signature is reused, 0 disables probes so only
+    /// open circuit breakers make a key unready.
+    pub probe_interval_secs: u64,
+}
+
+impl Default for ReadinessConfig {
+    fn default() -> Self {
+        Self {
+            probe_interval_secs: 30,
+        }
+    }
+}
+
+/// Operator API under `/admin`, disabled without a token.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Bearer token of the admin API, or `file:<path>`.
+    pub token: Option<Secret>,
+    /// File that keeps signing paused across restarts. Pauses are lost on
+    /// restart when unset.
+    pub pause_path: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct UpstreamConfig {
+    pub url: String,
+    /// Request timeout in seconds.
+    #[serde(default = "default_upstream_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_upstream_timeout() -> u64 {
+    30
+}
+
+impl Default for UpstreamConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            timeout_secs: default_upstream_timeout(),
+        }
+    }
+}
+
+impl ServiceConfig {
+    /// Read a configuration file. The format follows the file extension:
+    /// `.toml`, `.yaml`/`.yml` or `.json`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = Zeroizing::new(std::fs::read_to_string(path)?);
+        let value: Value = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&content)?,
+            Some("yaml" | "yml") => serde_yaml::from_str(&content)?,
+            Some("json") => serde_json::from_str(&content)?,
+            _ => {
+                return Err(Error::InvalidConfig(format!(
+                    "unknown format of '{}', expected .toml, .yaml or .json",
+                    path.display()
+                )));
+            }
+        };
+        Self::interpolated(value, |name| std::env::var(name).ok())
+    }
+
+    /// Interpolate environment variables into the string values of a parsed
+    /// file. Interpolating after parsing keeps a variable from adding keys or
+    /// changing the structure.
+    fn interpolated(mut value: Value, lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let config = visit_strings(&mut value, &mut |s| {
+            let interpolated = interpolate(s, &lookup)?;
+            s.zeroize();
+            *s = interpolated;
+            Ok(())
+        })
+        .and_then(|()| Ok(Self::deserialize(&value)?));
+        // The parsed copy may hold secrets, written or interpolated.
+        visit_strings(&mut value, &mut |s| {
+            s.zeroize();
+            Ok(())
+        })?;
+        config
+    }
+
+    /// Replace `file:` references in signer secrets with the file contents,
+    /// and apply the memory protections of the `secrets` section.
+    pub fn resolve_secrets(&mut self) -> Result<()> {
+        if self.secrets.disable_core_dumps {
+            crate::secret::disable_core_dumps()?;
+        }
+        let secrets = self
+            .signers
+            .iter_mut()
+            .flat_map(SignerConfig::secrets_mut)
+            .chain(
+                self.derivation
+                    .iter_mut()
+                    .flat_map(DerivationConfig::secrets_mut),
+            )
+            .chain(self.admin.token.as_mut());
+        for secret in secrets {
+            *secret = secret.resolve(self.secrets.strict_permissions)?;
+            if self.secrets.lock_memory {
+                secret.lock()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// JSON schema of the configuration file.
+    pub fn schema() -> serde_json::Value {
+        schemars::schema_for!(ServiceConfig).to_value()
+    }
+}
+
+/// Apply `f` to every string value in `value`, leaving keys alone.
+fn visit_strings(value: &mut Value, f: &mut impl FnMut(&mut String) -> Result<()>) -> Result<()> {
+    match value {
+        Value::String(s) => f(s),
+        Value::Array(items) => items.iter_mut().try_for_each(|item| visit_strings(item, f)),
+        Value::Object(map) => map.values_mut().try_for_each(|item| visit_strings(item, f)),
+        Value::Null | Value::Bool(_) | Value::Number(_) => Ok(()),
+    }
+}
+
+/// Replace `${NAME}` and `${NAME:-default}` with values from `lookup`.
+fn interpolate(input: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            output.push('$');
+            rest = after;
+            continue;
+        }
+        let Some(body) = rest.strip_prefix('{') else {
+            output.push('$');
+            continue;
+        };
+        let end = body
+            .find('}')
+            .ok_or_else(|| Error::InvalidConfig("unterminated '${'".to_string()))?;
+        let (name, default) = match body[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&body[..end], None),
+        };
+        let value = lookup(name)
+            .filter(|v| !v.is_empty() || default.is_none())
+            .or_else(|| default.map(str::to_string))
+            .ok_or_else(|| {
+                Error::InvalidConfig(format!("environment variable '{name}' is not set"))
+            })?;
+        output.push_str(&value);
+        rest = &body[end + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolate_env() {
+        let lookup = |name: &str| (name == "KEY").then(|| "secret".to_string());
+        assert_eq!(
+            interpolate("key = \"${KEY}\"", lookup).unwrap(),
+            "key = \"secret\""
+        );
+        assert_eq!(interpolate("${PORT:-8000}", lookup).unwrap(), "8000");
+        assert_eq!(interpolate("$$HOME $x", lookup).unwrap(), "$HOME $x");
+        assert!(interpolate("${MISSING}", lookup).is_err());
+    }
+
+    #[test]
+    fn interpolate_string_values_only() {
+        let injected = "0x01\"\n[policy]\nallowed_chains = [5]\n#";
+        let lookup = |name: &str| (name == "KEY").then(|| injected.to_string());
+        let toml = toml::from_str("[[signers]]\ntype = \"private_key\"\nkey = \"${KEY}\"\n");
+        let config = ServiceConfig::interpolated(toml.unwrap(), lookup).unwrap();
+        assert!(config.policy.allowed_chains.is_empty());
+        let SignerConfig::PrivateKey { key } = &config.signers[0] else {
+            panic!("private key signer expected");
+        };
+        assert_eq!(key.expose(), injected);
+
+        let yaml = serde_yaml::from_str("upstream:\n  url: ${URL:-http://node:8545}\n");
+        let config = ServiceConfig::interpolated(yaml.unwrap(), lookup).unwrap();
+        assert_eq!(config.upstream.unwrap().url, "http://node:8545");
+    }
+
+    #[test]
+    fn parse_formats() {
+        let toml: ServiceConfig = toml::from_str(
+            r#"
+            [[listeners]]
+            address = "127.0.0.1:9000"
+
+            [[signers]]
+            type = "private_key"
+            key = "0x01"
+
+            [policy]
+            allowed_chains = [1]
+            "#,
+        )
+        .unwrap();
+        let yaml: ServiceConfig = serde_yaml::from_str(
+            "listeners:\n  - address: 127.0.0.1:9000\nsigners:\n  - type: private_key\n    key: '0x01'\npolicy:\n  allowed_chains: [1]\n",
+        )
+        .unwrap();
+        assert_eq!(format!("{toml:?}"), format!("{yaml:?}"));
+        assert!(
+            serde_json::to_string(&toml).is_err(),
+            "secrets are not serialized"
+        );
+        assert_eq!(toml.idempotency.capacity, 1024);
+    }
+
+    #[test]
+    fn published_schema_is_current() {
+        let published: serde_json::Value =
+            serde_json::from_str(include_str!("../../config.schema.json")).unwrap();
+        assert_eq!(
+            published,
+            ServiceConfig::schema(),
+            "regenerate with `eth-signer config schema > crates/eth-signer/config.schema.json`"
+        );
+    }
+}
@@ -0,0 +1,583 @@
+mod file;
+pub use file::{ListenerConfig, ServiceConfig, UpstreamConfig};
+
+use crate::prelude::*;
+use clap::{Args, Parser, Subcommand};
+
+use crate::otel::{OtlpProtocol, TelemetryConfig};
+use crate::redact::Redaction;
+use crate::secret::{self, Secret};
+use crate::signer::{
+    AwsAssumeRole, DEFAULT_DERIVATION_PATH, MnemonicLanguage, RetryConfig, SignerConfig,
+};
+use alloy::primitives::Address;
+use std::{fmt, net::SocketAddr};
+
+#[cfg(debug_assertions)]
+const DEBUG: bool = true;
+
+#[cfg(not(debug_assertions))]
+const DEBUG: bool = false;
+
+/// Command line and environment options. Every option left unset falls back
+/// to the `--config` file, and then to the built-in default.
+#[derive(Parser, Debug, Clone)]
+#[command(version, about, long_about = None)]
+#[command(next_line_help = true)]
+#[command(subcommand_negates_reqs = true)]
+pub struct SignerOpts {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[arg(short, long, default_value_t = DEBUG)]
+    pub debug: bool,
+
+    /// Service configuration file in TOML, YAML or JSON.
+    #[arg(short, long, env = "SIGNER_CONFIG")]
+    pub config: Option<String>,
+
+    /// Listen on `0.0.0.0:<port>` instead of the configured listeners.
+    #[arg(long, env = "PORT")]
+    pub port: Option<u16>,
+
+    #[command(flatten)]
+    pub otel: OtelOpts,
+
+    /// How calldata and raw transactions appear in logs and spans [default: hash].
+    #[arg(long = "log.redact", env = "SIGNER_LOG_REDACT", value_enum)]
+    pub log_redact: Option<Redaction>,
+
+    /// Append-only audit log of every signing decision.
+    #[arg(long = "audit.path", env = "SIGNER_AUDIT_PATH")]
+    pub audit_path: Option<String>,
+    /// Start even if the audit log's hash chain is broken, recording the break.
+    #[arg(
+        long = "audit.allow_broken_chain",
+        env = "SIGNER_AUDIT_ALLOW_BROKEN_CHAIN",
+        num_args = 0..=1,
+        default_missing_value = "true"
+    )]
+    pub audit_allow_broken_chain: Option<bool>,
+
+    /// Number of signed responses kept for idempotent retries, 0 disables [default: 1024].
+    #[arg(long = "idempotency.capacity", env = "SIGNER_IDEMPOTENCY_CAPACITY")]
+    pub idempotency_capacity: Option<usize>,
+    /// Persist the idempotency cache so retries survive a restart.
+    #[arg(long = "idempotency.path", env = "SIGNER_IDEMPOTENCY_PATH")]
+    pub idempotency_path: Option<String>,
+
+    /// Refuse secret files other users can read instead of warning.
+    #[arg(
+        long = "secrets.strict_permissions",
+        env = "SIGNER_SECRETS_STRICT_PERMISSIONS",
+        num_args = 0..=1,
+        default_missing_value = "true"
+    )]
+    pub secrets_strict_permissions: Option<bool>,
+    /// Lock signer secrets in memory so they are never swapped out.
+    #[arg(
+        long = "secrets.lock_memory",
+        env = "SIGNER_SECRETS_LOCK_MEMORY",
+        num_args = 0..=1,
+        default_missing_value = "true"
+    )]
+    pub secrets_lock_memory: Option<bool>,
+    /// Disable core dumps of the signer process.
+    #[arg(
+        long = "secrets.disable_core_dumps",
+        env = "SIGNER_SECRETS_DISABLE_CORE_DUMPS",
+        num_args = 0..=1,
+        default_missing_value = "true"
+    )]
+    pub secrets_disable_core_dumps: Option<bool>,
+
+    /// Forward JSON-RPC methods the signer does not serve to this endpoint.
+    #[arg(long = "upstream.url", env = "SIGNER_UPSTREAM_URL")]
+    pub upstream_url: Option<String>,
+
+    /// Bearer token of the admin API, which is disabled without one.
+    #[arg(long = "admin.token", env = "SIGNER_ADMIN_TOKEN")]
+    pub admin_token: Option<Secret>,
+    /// File that keeps signing paused across restarts.
+    #[arg(long = "admin.pause_path", env = "SIGNER_ADMIN_PAUSE_PATH")]
+    pub admin_pause_path: Option<String>,
+
+    /// Seconds a key's readiness probe is reused, 0 disables probes [default: 30].
+    #[arg(
+        long = "readiness.probe_interval_secs",
+        env = "SIGNER_READINESS_PROBE_INTERVAL_SECS"
+    )]
+    pub readiness_probe_interval_secs: Option<u64>,
+
+    /// Signer type. Replaces the signers of the config file when set.
+    #[arg(
+        name = "type",
+        short = 't',
+        long,
+        env = "SIGNER_TYPE",
+        required_unless_present = "config"
+    )]
+    _type: Option<String>,
+
+    #[arg(long, env = "SIGNER_PRIVATE_KEY")]
+    private_key: Option<Secret>,
+    /// File holding the private key, e.g. a Docker or Kubernetes secret.
+    #[arg(long, env = "SIGNER_PRIVATE_KEY_FILE", conflicts_with = "private_key")]
+    private_key_file: Option<String>,
+
+    #[arg(long, env = "SIGNER_MNEMONIC")]
+    mnemonic: Option<Secret>,
+    /// File holding the mnemonic phrase.
+    #[arg(long, env = "SIGNER_MNEMONIC_FILE", conflicts_with = "mnemonic")]
+    mnemonic_file: Option<String>,
+    /// BIP-39 passphrase of the mnemonic.
+    #[arg(long = "mnemonic.passphrase", env = "SIGNER_MNEMONIC_PASSPHRASE")]
+    mnemonic_passphrase: Option<Secret>,
+    #[arg(
+        long = "mnemonic.passphrase_file",
+        env = "SIGNER_MNEMONIC_PASSPHRASE_FILE",
+        conflicts_with = "mnemonic_passphrase"
+    )]
+    mnemonic_passphrase_file: Option<String>,
+    /// Wordlist of the mnemonic [default: english].
+    #[arg(
+        long = "mnemonic.language",
+        env = "SIGNER_MNEMONIC_LANGUAGE",
+        value_enum
+    )]
+    mnemonic_language: Option<MnemonicLanguage>,
+    /// Derivation path prefix; the account index is appended [default: m/44'/60'/0'/0].
+    #[arg(
+        long = "mnemonic.derivation_path",
+        env = "SIGNER_MNEMONIC_DERIVATION_PATH"
+    )]
+    mnemonic_derivation_path: Option<String>,
+    /// First account index to derive [default: 0].
+    #[arg(long = "mnemonic.index", env = "SIGNER_MNEMONIC_INDEX")]
+    mnemonic_index: Option<u32>,
+    /// Number of consecutive accounts to derive [default: 1].
+    #[arg(long = "mnemonic.count", env = "SIGNER_MNEMONIC_COUNT")]
+    mnemonic_count: Option<u32>,
+
+    #[arg(long = "keystore.path", env = "SIGNER_KEYSTORE_PATH")]
+    keystore_path: Option<String>,
+    #[arg(long = "keystore.password", env = "SIGNER_KEYSTORE_PASSWORD")]
+    keystore_password: Option<Secret>,
+    /// File holding the keystore password.
+    #[arg(
+        long = "keystore.password_file",
+        env = "SIGNER_KEYSTORE_PASSWORD_FILE",
+        conflicts_with = "keystore_password"
+    )]
+    keystore_password_file: Option<String>,
+
+    #[arg(long = "azurekeyvault.key", env = "SIGNER_AZUREKEYVAULT_KEY")]
+    azurekeyvault_key: Option<String>,
+
+    /// Key ID or ARN, or an alias as `alias/<name>`.
+    #[arg(long = "awskms.key", env = "SIGNER_AWSKMS_KEY")]
+    awskms_key: Option<String>,
+    /// Key alias, instead of `--awskms.key`.
+    #[arg(
+        long = "awskms.alias",
+        env = "SIGNER_AWSKMS_ALIAS",
+        conflicts_with = "awskms_key"
+    )]
+    awskms_alias: Option<String>,
+    #[arg(long = "awskms.region", env = "SIGNER_AWSKMS_REGION")]
+    awskms_region: Option<String>,
+    /// Endpoint override, e.g. LocalStack.
+    #[arg(long = "awskms.endpoint", env = "SIGNER_AWSKMS_ENDPOINT")]
+    awskms_endpoint: Option<String>,
+    #[arg(long = "awskms.profile", env = "SIGNER_AWSKMS_PROFILE")]
+    awskms_profile: Option<String>,
+    /// Role to assume before calling KMS.
+    #[arg(long = "awskms.role_arn", env = "SIGNER_AWSKMS_ROLE_ARN")]
+    awskms_role_arn: Option<String>,
+    #[arg(
+        long = "awskms.external_id",
+        env = "SIGNER_AWSKMS_EXTERNAL_ID",
+        requires = "awskms_role_arn"
+    )]
+    awskms_external_id: Option<String>,
+    /// Chain the key signs for, 0 for any chain [default: 1].
+    #[arg(long = "awskms.chain_id", env = "SIGNER_AWSKMS_CHAIN_ID")]
+    awskms_chain_id: Option<u64>,
+
+    #[arg(long = "gcpkms.project_id", env = "SIGNER_GCPKMS_PROJECT_ID")]
+    gcpkms_project_id: Option<String>,
+    #[arg(long = "gcpkms.location", env = "SIGNER_GCPKMS_LOCATION")]
+    gcpkms_location: Option<String>,
+    #[arg(long = "gcpkms.key_ring", env = "SIGNER_GCPKMS_KEY_RING")]
+    gcpkms_key_ring: Option<String>,
+    #[arg(long = "gcpkms.key", env = "SIGNER_GCPKMS_KEY")]
+    gcpkms_key: Option<String>,
+    /// Key version, the newest enabled one when unset.
+    #[arg(long = "gcpkms.version", env = "SIGNER_GCPKMS_VERSION")]
+    gcpkms_version: Option<u64>,
+    /// Endpoint override, e.g. an emulator.
+    #[arg(long = "gcpkms.endpoint", env = "SIGNER_GCPKMS_ENDPOINT")]
+    gcpkms_endpoint: Option<String>,
+    /// Service account key file.
+    #[arg(
+        long = "gcpkms.credentials_file",
+        env = "SIGNER_GCPKMS_CREDENTIALS_FILE"
+    )]
+    gcpkms_credentials_file: Option<String>,
+    /// Seconds between checks for a newer key version [default: 300].
+    #[arg(
+        long = "gcpkms.refresh_interval_secs",
+        env = "SIGNER_GCPKMS_REFRESH_INTERVAL_SECS"
+    )]
+    gcpkms_refresh_interval_secs: Option<u64>,
+}
+
+/// OpenTelemetry export settings. Names follow the standard `OTEL_*`
+/// environment variables.
+#[derive(Args, Clone)]
+pub struct OtelOpts {
+    /// OTLP collector endpoint. Nothing is exported over OTLP when unset.
+    #[arg(long, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    pub otel_exporter_otlp_endpoint: Option<String>,
+
+    /// Export protocol for all signals [default: http/protobuf].
+    #[arg(long = "otel.protocol", env = "OTEL_EXPORTER_OTLP_PROTOCOL")]
+    pub protocol: Option<OtlpProtocol>,
+    #[arg(
+        long = "otel.traces_protocol",
+        env = "OTEL_EXPORTER_OTLP_TRACES_PROTOCOL"
+    )]
+    pub traces_protocol: Option<OtlpProtocol>,
+    #[arg(
+        long = "otel.metrics_protocol",
+        env = "OTEL_EXPORTER_OTLP_METRICS_PROTOCOL"
+    )]
+    pub metrics_protocol: Option<OtlpProtocol>,
+    #[arg(long = "otel.logs_protocol", env = "OTEL_EXPORTER_OTLP_LOGS_PROTOCOL")]
+    pub logs_protocol: Option<OtlpProtocol>,
+
+    /// Headers sent with every export, as comma separated `key=value` pairs.
+    #[arg(
+        long = "otel.headers",
+        env = "OTEL_EXPORTER_OTLP_HEADERS",
+        value_delimiter = ',',
+        value_parser = parse_key_value
+    )]
+    pub headers: Vec<(String, String)>,
+
+    /// Fraction of root traces sampled; child spans follow their parent [default: 1.0].
+    #[arg(long = "otel.sampling_ratio", env = "OTEL_TRACES_SAMPLER_ARG")]
+    pub sampling_ratio: Option<f64>,
+
+    /// `service.name` resource attribute [default: eth-signer].
+    #[arg(long = "otel.service_name", env = "OTEL_SERVICE_NAME")]
+    pub service_name: Option<String>,
+    /// Extra resource attributes, as comma separated `key=value` pairs.
+    #[arg(
+        long = "otel.resource_attributes",
+        env = "OTEL_RESOURCE_ATTRIBUTES",
+        value_delimiter = ',',
+        value_parser = parse_key_value
+    )]
+    pub resource_attributes: Vec<(String, String)>,
+}
+
+impl OtelOpts {
+    fn apply(self, config: &mut TelemetryConfig) {
+        if let Some(endpoint) = self.otel_exporter_otlp_endpoint {
+            config.endpoint = Some(endpoint);
+        }
+        if let Some(protocol) = self.protocol {
+            config.protocol = protocol;
+        }
+        if let Some(protocol) = self.traces_protocol {
+            config.traces_protocol = Some(protocol);
+        }
+        if let Some(protocol) = self.metrics_protocol {
+            config.metrics_protocol = Some(protocol);
+        }
+        if let Some(protocol) = self.logs_protocol {
+            config.logs_protocol = Some(protocol);
+        }
+        config.headers.extend(self.headers);
+        if let Some(ratio) = self.sampling_ratio {
+            config.sampling_ratio = ratio;
+        }
+        if let Some(name) = self.service_name {
+            config.service_name = name;
+        }
+        config.resource_attributes.extend(self.resource_attributes);
+    }
+}
+
+impl fmt::Debug for OtelOpts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Export headers commonly carry collector credentials.
+        let header_names: Vec<&str> = self.headers.iter().map(|(k, _)| k.as_str()).collect();
+        f.debug_struct("OtelOpts")
+            .field(
+                "otel_exporter_otlp_endpoint",
+                &self.otel_exporter_otlp_endpoint,
+            )
+            .field("protocol", &self.protocol)
+            .field("traces_protocol", &self.traces_protocol)
+            .field("metrics_protocol", &self.metrics_protocol)
+            .field("logs_protocol", &self.logs_protocol)
+            .field("headers", &header_names)
+            .field("sampling_ratio", &self.sampling_ratio)
+            .field("service_name", &self.service_name)
+            .field("resource_attributes", &self.resource_attributes)
+            .finish()
+    }
+}
+
+fn parse_key_value(s: &str) -> std::result::Result<(String, String), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected key=value, got '{s}'"))?;
+    Ok((key.trim().to_string(), value.trim().to_string()))
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Inspect the signing audit log.
+    #[command(subcommand)]
+    Audit(AuditCommand),
+    /// Validate configuration files.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Split a private key or mnemonic, read from stdin, into the Shamir
+    /// shares of a `sealed` signer, one per line.
+    SplitSecret {
+        /// Shares needed to reconstruct the secret.
+        #[arg(long)]
+        threshold: u8,
+        /// Shares to print.
+        #[arg(long)]
+        shares: u8,
+    },
+    /// Submit an unseal share, read from stdin, to a running service.
+    Unseal(SealArgs),
+    /// Wipe the key of a running service's sealed signer.
+    Seal(SealArgs),
+    /// Stop a running service from signing, entirely or for one key or
+    /// method, until it is resumed.
+    Pause {
+        #[command(flatten)]
+        admin: AdminArgs,
+        #[command(flatten)]
+        target: PauseTarget,
+        /// Reason recorded with the pause.
+        #[arg(long, default_value = "")]
+        reason: String,
+    },
+    /// Lift a pause set with `pause`.
+    Resume {
+        #[command(flatten)]
+        admin: AdminArgs,
+        #[command(flatten)]
+        target: PauseTarget,
+    },
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct AdminArgs {
+    /// URL of the running service.
+    #[arg(long, env = "SIGNER_URL", default_value = "http://127.0.0.1:8000")]
+    pub url: String,
+    /// Admin API token, or `file:<path>`.
+    #[arg(long, env = "SIGNER_ADMIN_TOKEN")]
+    pub token: Secret,
+}
+
+/// Scope of a pause; all signing when neither is given.
+#[derive(Args, Debug, Clone)]
+pub struct PauseTarget {
+    /// Only this key.
+    #[arg(long)]
+    pub key: Option<Address>,
+    /// Only this JSON-RPC method.
+    #[arg(long, conflicts_with = "key")]
+    pub method: Option<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SealArgs {
+    /// URL of the running service.
+    #[arg(long, env = "SIGNER_URL", default_value = "http://127.0.0.1:8000")]
+    pub url: String,
+    /// Token of the sealed signer, or `file:<path>`.
+    #[arg(long, env = "SIGNER_SEAL_TOKEN")]
+    pub token: Secret,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum AuditCommand {
+    /// Verify the hash chain of an audit log.
+    Verify {
+        #[arg(long, env = "SIGNER_AUDIT_PATH")]
+        path: String,
+    },
+    /// Print the records in a sequence range as JSON lines.
+    Export {
+        #[arg(long, env = "SIGNER_AUDIT_PATH")]
+        path: String,
+        #[arg(long)]
+        from: Option<u64>,
+        #[arg(long)]
+        to: Option<u64>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommand {
+    /// Print the JSON schema of the configuration file.
+    Schema,
+    /// Parse a configuration file, including environment interpolation.
+    Check {
+        #[arg(long, env = "SIGNER_CONFIG")]
+        path: String,
+    },
+}
+
+impl SignerOpts {
+    /// Load the `--config` file, if any, and layer the command line and
+    /// environment options on top of it.
+    pub fn into_config(self) -> Result<ServiceConfig> {
+        let mut config = match &self.config {
+            Some(path) => ServiceConfig::load(path)?,
+            None => ServiceConfig::default(),
+        };
+
+        if let Some(port) = self.port {
+            config.listeners = vec![ListenerConfig {
+                address: SocketAddr::from(([0, 0, 0, 0], port)),
+            }];
+        }
+        if let Some(redact) = self.log_redact {
+            config.log.redact = redact;
+        }
+        if let Some(path) = &self.audit_path {
+            config.audit.path = Some(path.clone());
+        }
+        if let Some(allow) = self.audit_allow_broken_chain {
+            config.audit.allow_broken_chain = allow;
+        }
+        if let Some(capacity) = self.idempotency_capacity {
+            config.idempotency.capacity = capacity;
+        }
+        if let Some(path) = &self.idempotency_path {
+            config.idempotency.path = Some(path.clone());
+        }
+        if let Some(strict) = self.secrets_strict_permissions {
+            config.secrets.strict_permissions = strict;
+        }
+        if let Some(lock) = self.secrets_lock_memory {
+            config.secrets.lock_memory = lock;
+        }
+        if let Some(disable) = self.secrets_disable_core_dumps {
+            config.secrets.disable_core_dumps = disable;
+        }
+        if let Some(url) = &self.upstream_url {
+            config.upstream = Some(UpstreamConfig {
+                url: url.clone(),
+                ..config.upstream.unwrap_or_default()
+            });
+        }
+        if let Some(token) = &self.admin_token {
+            config.admin.token = Some(token.clone());
+        }
+        if let Some(path) = &self.admin_pause_path {
+            config.admin.pause_path = Some(path.clone());
+        }
+        if let Some(interval) = self.readiness_probe_interval_secs {
+            config.readiness.probe_interval_secs = interval;
+        }
+        self.otel.clone().apply(&mut config.telemetry);
+
+        if self._type.is_some() {
+            config.signers = vec![self.try_into()?];
+        }
+        if config.signers.is_empty() {
+            return Err(Error::RequireConfigKeyNotFound("signers"));
+        }
+
+        Ok(config)
+    }
+}
+
+impl TryInto<SignerConfig> for SignerOpts {
+    type Error = Error;
+
+    fn try_into(self) -> Result<SignerConfig> {
+        let _type = self._type.ok_or(Error::RequireConfigKeyNotFound("type"))?;
+        // `*_FILE` options become `file:` references, resolved with the
+        // secrets of the config file.
+        let file_ref = |path: String| Secret::new(format!("{}{path}", secret::FILE_PREFIX));
+        let private_key = self.private_key.or(self.private_key_file.map(file_ref));
+        let mnemonic = self.mnemonic.or(self.mnemonic_file.map(file_ref));
+        let mnemonic_passphrase = self
+            .mnemonic_passphrase
+            .or(self.mnemonic_passphrase_file.map(file_ref));
+        let keystore_password = self
+            .keystore_password
+            .or(self.keystore_password_file.map(file_ref));
+        match _type.as_str() {
+            "private_key" => Ok(SignerConfig::PrivateKey {
+                key: private_key.ok_or(Error::RequireConfigKeyNotFound("private_key"))?,
+            }),
+            "mnemonic" => Ok(SignerConfig::Mnemonic {
+                phrase: mnemonic.ok_or(Error::RequireConfigKeyNotFound("mnemonic"))?,
+                passphrase: mnemonic_passphrase,
+                language: self.mnemonic_language.unwrap_or_default(),
+                derivation_path: self
+                    .mnemonic_derivation_path
+                    .unwrap_or_else(|| DEFAULT_DERIVATION_PATH.to_string()),
+                index: self.mnemonic_index.unwrap_or(0),
+                count: self.mnemonic_count.unwrap_or(1),
+            }),
+            "keystore" => Ok(SignerConfig::KeyStore {
+                path: self
+                    .keystore_path
+                    .ok_or(Error::RequireConfigKeyNotFound("keystore.path"))?,
+                password: keystore_password
+                    .ok_or(Error::RequireConfigKeyNotFound("keytore.password"))?,
+            }),
+            "awskms" => Ok(SignerConfig::AwsKms {
+                key: self.awskms_key,
+                alias: self.awskms_alias,
+                region: self.awskms_region,
+                endpoint: self.awskms_endpoint,
+                profile: self.awskms_profile,
+                credentials: None,
+                assume_role: self.awskms_role_arn.map(|role_arn| AwsAssumeRole {
+                    role_arn,
+                    external_id: self.awskms_external_id,
+                    session_name: None,
+                }),
+                chain_id: self.awskms_chain_id.unwrap_or(1),
+                retry: RetryConfig::default(),
+            }),
+            "gcpkms" => Ok(SignerConfig::GoogleKms {
+                project_id: self
+                    .gcpkms_project_id
+                    .ok_or(Error::RequireConfigKeyNotFound("gcpkms.project_id"))?,
+                location: self
+                    .gcpkms_location
+                    .ok_or(Error::RequireConfigKeyNotFound("gcpkms.location"))?,
+                key_ring: self
+                    .gcpkms_key_ring
+                    .ok_or(Error::RequireConfigKeyNotFound("gcpkms.key_ring"))?,
+                key: self
+                    .gcpkms_key
+                    .ok_or(Error::RequireConfigKeyNotFound("gcpkms.key"))?,
+                version: self.gcpkms_version,
+                endpoint: self.gcpkms_endpoint,
+                credentials_file: self.gcpkms_credentials_file,
+                refresh_interval_secs: self.gcpkms_refresh_interval_secs.unwrap_or(300),
+                retry: RetryConfig::default(),
+            }),
+            _ => Err(Error::InvalidSignerType(_type)),
+        }
+    }
+}
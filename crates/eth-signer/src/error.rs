@@ -1,5 +1,6 @@
 use alloy::{
     network::Ethereum,
+    primitives::Address,
     rpc::json_rpc::ErrorPayload,
     signers::{
         aws::AwsSignerError,
@@ -28,17 +29,98 @@ pub enum Error {
     #[error(transparent)]
     GcloudSDK(#[from] gcloud_sdk::error::Error),
 
+    #[error(transparent)]
+    Grpc(#[from] Box<gcloud_sdk::tonic::Status>),
+
+    #[error(transparent)]
+    Pkcs11(#[from] cryptoki::error::Error),
+
     #[error(transparent)]
     TransactionBuilder(#[from] alloy::network::TransactionBuilderError<Ethereum>),
 
     #[error(transparent)]
     SerdeJson(#[from] serde_json::Error),
 
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+
+    #[error(transparent)]
+    Yaml(#[from] serde_yaml::Error),
+
+    #[error(transparent)]
+    Upstream(#[from] alloy::transports::http::reqwest::Error),
+
+    #[error(transparent)]
+    Hex(#[from] alloy::hex::FromHexError),
+
+    #[error(transparent)]
+    Eip2718(#[from] alloy::eips::eip2718::Eip2718Error),
+
     #[error("Invalid signer type '{0}'")]
     InvalidSignerType(String),
 
     #[error("Require config key '{0}' not found")]
     RequireConfigKeyNotFound(&'static str),
+
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
+
+    #[error("Secret file '{0}' is accessible by other users")]
+    InsecureSecretFile(String),
+
+    #[error("Keystore '{0}' does not hold the recorded address {1}")]
+    KeystoreAddressMismatch(String, Address),
+
+    #[error("Signer {0} is configured more than once")]
+    DuplicateSigner(Address),
+
+    #[error("No signer for {0}")]
+    UnknownSigner(Address),
+
+    #[error("Derivation path '{0}' is outside the allowed subtree")]
+    InvalidDerivationPath(String),
+
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+
+    #[error("Invalid blob transaction: {0}")]
+    InvalidBlob(String),
+
+    #[error("Vault: {0}")]
+    Vault(String),
+
+    #[error("{0} call timed out after {1:?}")]
+    Timeout(&'static str, std::time::Duration),
+
+    #[error("{0} circuit is open after repeated failures")]
+    CircuitOpen(&'static str),
+
+    #[error("Signer is sealed")]
+    Sealed,
+
+    #[error("Invalid unseal share: {0}")]
+    InvalidShare(String),
+
+    #[error("Signing is paused for {0}")]
+    Paused(String),
+
+    #[error("Admin request failed: {0}")]
+    AdminRequest(String),
+
+    #[error("Self check failed: {0}")]
+    SelfCheck(String),
+
+    #[error("Policy violation: {0}")]
+    PolicyViolation(String),
+
+    #[error("Audit chain broken at record {0}")]
+    AuditChainBroken(u64),
+
+    #[error("Idempotency key '{0}' was already used for a different request")]
+    IdempotencyKeyReused(String),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -53,8 +135,27 @@ impl IntoResponse for Error {
     }
 }
 
+/// JSON-RPC error code of requests for a key that is still sealed, EIP-1474's
+/// "resource unavailable".
+pub const SEALED_ERROR_CODE: i64 = -32002;
+
+/// JSON-RPC error code of requests refused by the kill switch.
+pub const PAUSED_ERROR_CODE: i64 = -32020;
+
 impl From<Error> for ErrorPayload {
     fn from(error: Error) -> ErrorPayload {
-        ErrorPayload::internal_error_message(error.to_string().into())
+        match error {
+            Error::Sealed => ErrorPayload {
+                code: SEALED_ERROR_CODE,
+                message: error.to_string().into(),
+                data: None,
+            },
+            Error::Paused(_) => ErrorPayload {
+                code: PAUSED_ERROR_CODE,
+                message: error.to_string().into(),
+                data: None,
+            },
+            _ => ErrorPayload::internal_error_message(error.to_string().into()),
+        }
     }
 }
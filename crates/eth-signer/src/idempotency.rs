@@ -0,0 +1,329 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use alloy::primitives::B256;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+use crate::prelude::*;
+
+/// A previously produced response, keyed by idempotency key.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CachedResponse {
+    key: String,
+    request_hash: B256,
+    raw: String,
+}
+
+/// A key whose request is being signed. Retries of the same request wait on
+/// `slot` instead of signing again.
+struct Pending {
+    request_hash: B256,
+    slot: Arc<AsyncMutex<()>>,
+}
+
+struct Entries {
+    by_key: HashMap<String, CachedResponse>,
+    order: VecDeque<String>,
+    pending: HashMap<String, Pending>,
+    file: Option<File>,
+    /// Lines in the backing file, including ones already evicted.
+    lines: usize,
+}
+
+/// Bounded cache of signed responses. Optionally persisted as a JSON lines
+/// file so retries survive a restart.
+pub struct IdempotencyCache {
+    capacity: usize,
+    path: Option<PathBuf>,
+    entries: Mutex<Entries>,
+}
+
+impl IdempotencyCache {
+    pub fn new(capacity: usize, path: Option<impl AsRef<Path>>) -> Result<Self> {
+        let path = path.map(|p| p.as_ref().to_path_buf());
+        let mut entries = Entries {
+            by_key: HashMap::new(),
+            order: VecDeque::new(),
+            pending: HashMap::new(),
+            file: None,
+            lines: 0,
+        };
+
+        if let Some(path) = &path {
+            match File::open(path) {
+                Ok(file) => {
+                    for line in BufReader::new(file).lines() {
+                        let line = line?;
+                        entries.lines += 1;
+                        match serde_json::from_str::<CachedResponse>(&line) {
+                            Ok(cached) => entries.insert(cached, capacity),
+                            Err(e) => tracing::warn!("skip corrupt idempotency entry: {}", e),
+                        }
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+            entries.file = Some(OpenOptions::new().create(true).append(true).open(path)?);
+        }
+
+        let cache = Self {
+            capacity,
+            path,
+            entries: Mutex::new(entries),
+        };
+        cache.compact_if_needed(&mut cache.entries.lock().expect("lock poisoned"))?;
+        Ok(cache)
+    }
+
+    /// Return the cached response for `key`, or reserve the key so that only
+    /// the caller signs it. A retry arriving while the first request is still
+    /// being signed waits for it and gets its response; a different request
+    /// under the same key is refused.
+    pub async fn reserve(&self, key: &str, request_hash: B256) -> Result<Reservation<'_>> {
+        let slot = {
+            let mut entries = self.entries.lock().expect("lock poisoned");
+            if let Some(raw) = entries.get(key, request_hash)? {
+                return Ok(Reservation::Cached(raw));
+            }
+            match entries.pending.get(key) {
+                Some(pending) if pending.request_hash != request_hash => {
+                    return Err(Error::IdempotencyKeyReused(key.to_string()));
+                }
+                Some(pending) => pending.slot.clone(),
+                None => {
+                    let slot = Arc::new(AsyncMutex::new(()));
+                    let pending = Pending {
+                        request_hash,
+                        slot: slot.clone(),
+                    };
+                    entries.pending.insert(key.to_string(), pending);
+                    slot
+                }
+            }
+        };
+
+        let guard = slot.lock_owned().await;
+        // The request may have been signed while this one waited.
+        let mut entries = self.entries.lock().expect("lock poisoned");
+        if let Some(cached) = entries.get(key, request_hash).transpose() {
+            entries.release(key, &guard);
+            return cached.map(Reservation::Cached);
+        }
+        drop(entries);
+        Ok(Reservation::Pending(PendingKey {
+            cache: self,
+            key: key.to_string(),
+            request_hash,
+            guard,
+        }))
+    }
+
+    pub fn insert(&self, key: String, request_hash: B256, raw: String) -> Result<()> {
+        let cached = CachedResponse {
+            key,
+            request_hash,
+            raw,
+        };
+
+        let mut entries = self.entries.lock().expect("lock poisoned");
+        if let Some(file) = entries.file.as_mut() {
+            let mut line = serde_json::to_vec(&cached)?;
+            line.push(b'\n');
+            file.write_all(&line)?;
+            // A response is only returned once a restart would still find it.
+            file.sync_data()?;
+            entries.lines += 1;
+        }
+        entries.insert(cached, self.capacity);
+        self.compact_if_needed(&mut entries)
+    }
+
+    /// Rewrite the backing file with only the retained entries once evicted
+    /// ones make up more than half of it.
+    fn compact_if_needed(&self, entries: &mut Entries) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if entries.lines <= self.capacity.max(1) * 2 {
+            return Ok(());
+        }
+
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        for key in &entries.order {
+            let mut line = serde_json::to_vec(&entries.by_key[key])?;
+            line.push(b'\n');
+            file.write_all(&line)?;
+        }
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+
+        entries.file = Some(OpenOptions::new().append(true).open(path)?);
+        entries.lines = entries.order.len();
+        Ok(())
+    }
+}
+
+/// Outcome of [`IdempotencyCache::reserve`].
+pub enum Reservation<'a> {
+    /// The response produced for an earlier identical request.
+    Cached(String),
+    /// The caller holds the key and must sign.
+    Pending(PendingKey<'a>),
+}
+
+/// A reserved key. Completing it caches the response for waiting and later
+/// retries; dropping it without completing lets the next retry sign.
+pub struct PendingKey<'a> {
+    cache: &'a IdempotencyCache,
+    key: String,
+    request_hash: B256,
+    guard: OwnedMutexGuard<()>,
+}
+
+impl PendingKey<'_> {
+    pub fn complete(self, raw: String) -> Result<()> {
+        self.cache.insert(self.key.clone(), self.request_hash, raw)
+    }
+}
+
+impl Drop for PendingKey<'_> {
+    fn drop(&mut self) {
+        let mut entries = self.cache.entries.lock().expect("lock poisoned");
+        entries.release(&self.key, &self.guard);
+    }
+}
+
+impl Entries {
+    /// Look up the response for `key`. A key reused with a different request
+    /// is refused rather than answered with another transaction's signature.
+    fn get(&self, key: &str, request_hash: B256) -> Result<Option<String>> {
+        match self.by_key.get(key) {
+            Some(cached) if cached.request_hash != request_hash => {
+                Err(Error::IdempotencyKeyReused(key.to_string()))
+            }
+            Some(cached) => Ok(Some(cached.raw.clone())),
+            None => Ok(None),
+        }
+    }
+
+    /// Forget the pending request of `key` once `guard` holds its last slot.
+    /// Slots are only cloned under the entries lock, so nobody else holds one
+    /// when only the map and `guard` do.
+    fn release(&mut self, key: &str, guard: &OwnedMutexGuard<()>) {
+        if Arc::strong_count(OwnedMutexGuard::mutex(guard)) == 2 {
+            self.pending.remove(key);
+        }
+    }
+
+    fn insert(&mut self, cached: CachedResponse, capacity: usize) {
+        if self
+            .by_key
+            .insert(cached.key.clone(), cached.clone())
+            .is_none()
+        {
+            self.order.push_back(cached.key);
+        }
+        while self.order.len() > capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.by_key.remove(&evicted);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    impl IdempotencyCache {
+        fn get(&self, key: &str, request_hash: B256) -> Result<Option<String>> {
+            self.entries
+                .lock()
+                .expect("lock poisoned")
+                .get(key, request_hash)
+        }
+    }
+
+    #[test]
+    fn replay_and_eviction() {
+        let cache = IdempotencyCache::new(2, None::<&str>).unwrap();
+        let (a, b) = (B256::with_last_byte(1), B256::with_last_byte(2));
+
+        cache.insert("a".into(), a, "0x01".into()).unwrap();
+        assert_eq!(cache.get("a", a).unwrap().as_deref(), Some("0x01"));
+        assert!(matches!(
+            cache.get("a", b),
+            Err(Error::IdempotencyKeyReused(_))
+        ));
+
+        cache.insert("b".into(), b, "0x02".into()).unwrap();
+        cache.insert("c".into(), b, "0x03".into()).unwrap();
+        assert_eq!(cache.get("a", a).unwrap(), None);
+        assert_eq!(cache.get("c", b).unwrap().as_deref(), Some("0x03"));
+    }
+
+    #[tokio::test]
+    async fn concurrent_retries_sign_once() {
+        let cache = IdempotencyCache::new(2, None::<&str>).unwrap();
+        let (a, b) = (B256::with_last_byte(1), B256::with_last_byte(2));
+
+        let Reservation::Pending(first) = cache.reserve("a", a).await.unwrap() else {
+            panic!("nothing is cached yet");
+        };
+        assert!(matches!(
+            cache.reserve("a", b).await,
+            Err(Error::IdempotencyKeyReused(_))
+        ));
+        let retry = tokio::time::timeout(Duration::from_millis(50), cache.reserve("a", a));
+        assert!(retry.await.is_err(), "a retry waits for the first request");
+
+        let (completed, retry) = tokio::join!(
+            async { first.complete("0x01".into()) },
+            cache.reserve("a", a)
+        );
+        completed.unwrap();
+        assert!(matches!(retry.unwrap(), Reservation::Cached(raw) if raw == "0x01"));
+
+        // A request that failed to sign leaves the key free for the next retry.
+        drop(cache.reserve("b", b).await.unwrap());
+        assert!(matches!(
+            cache.reserve("b", b).await.unwrap(),
+            Reservation::Pending(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn waiting_retry_frees_the_key() {
+        let cache = IdempotencyCache::new(1, None::<&str>).unwrap();
+        let (a, b) = (B256::with_last_byte(1), B256::with_last_byte(2));
+
+        let Reservation::Pending(first) = cache.reserve("a", a).await.unwrap() else {
+            panic!("nothing is cached yet");
+        };
+        let mut retry = Box::pin(cache.reserve("a", a));
+        let waiting = tokio::time::timeout(Duration::from_millis(50), &mut retry);
+        assert!(
+            waiting.await.is_err(),
+            "a retry waits for the first request"
+        );
+        first.complete("0x01".into()).unwrap();
+        assert!(matches!(retry.await.unwrap(), Reservation::Cached(raw) if raw == "0x01"));
+        assert!(cache.entries.lock().unwrap().pending.is_empty());
+
+        // Once the response is evicted, the key is free for another request.
+        cache.insert("b".into(), b, "0x02".into()).unwrap();
+        assert!(matches!(
+            cache.reserve("a", b).await.unwrap(),
+            Reservation::Pending(_)
+        ));
+    }
+}
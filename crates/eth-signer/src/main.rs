@@ -1,65 +1,224 @@
+mod audit;
+mod blob;
 mod config;
 mod error;
+mod idempotency;
+mod metrics;
 mod otel;
+mod pause;
+mod policy;
 mod prelude;
+mod redact;
 mod route;
+mod secret;
 mod signer;
+mod upstream;
+mod verify;
 
-use axum::{
-    Router,
-    extract::{MatchedPath, Request},
-};
+use alloy::transports::http::reqwest;
+use axum::Router;
 use clap::Parser;
-use std::net::SocketAddr;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::net::TcpListener;
 use tokio::signal;
+use tokio::sync::watch;
 use tower_http::trace::TraceLayer;
+use zeroize::Zeroizing;
 
 #[tokio::main]
 async fn main() -> prelude::Result<()> {
     let args = config::SignerOpts::parse();
 
-    otel::init(args.debug);
-
-    let port = std::env::var("PORT")
-        .ok()
-        .and_then(|p| p.parse().ok())
-        .unwrap_or(8000);
-
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    let lisenter = TcpListener::bind(&addr).await.unwrap();
-    tracing::info!("listening on {}", addr);
-
-    let signer_config: signer::SignerConfig = args.try_into()?;
-    tracing::info!("signer config: {:?}", signer_config);
-
-    let routes = route::routes(signer_config.clone());
-    let app = Router::new().merge(routes).layer(
-        TraceLayer::new_for_http()
-            .make_span_with(|req: &Request| {
-                let method = req.method();
-                let uri = req.uri();
-                // axum automatically adds this extension.
-                let matched_path = req
-                    .extensions()
-                    .get::<MatchedPath>()
-                    .map(|matched_path| matched_path.as_str());
-                tracing::debug_span!("request", %method, %uri, matched_path)
+    if let Some(command) = args.command {
+        return run_command(command).await;
+    }
+
+    let debug = args.debug;
+    let mut config = args.into_config()?;
+    let telemetry = otel::init(debug, &config.telemetry);
+    // Resolved once logging is up, so permission warnings are visible.
+    config.resolve_secrets()?;
+    tracing::info!("service config: {:?}", config);
+
+    let audit = match &config.audit.path {
+        Some(path) => Some(Arc::new(audit::AuditLog::open(
+            path,
+            config.audit.allow_broken_chain,
+        )?)),
+        None => None,
+    };
+
+    let idempotency = match config.idempotency.capacity {
+        0 => None,
+        capacity => Some(Arc::new(idempotency::IdempotencyCache::new(
+            capacity,
+            config.idempotency.path.as_ref(),
+        )?)),
+    };
+
+    let upstream = match &config.upstream {
+        Some(upstream) => Some(Arc::new(upstream::Upstream::new(upstream)?)),
+        None => None,
+    };
+
+    let kill_switch = Arc::new(pause::KillSwitch::open(config.admin.pause_path.as_ref())?);
+
+    let keys =
+        Arc::new(signer::KeyRing::new(&config.signers, config.secrets.strict_permissions).await?);
+    keys.watch();
+    let hd = match &config.derivation {
+        Some(derivation) => Some(Arc::new(signer::HdKeys::new(derivation)?)),
+        None => None,
+    };
+    tracing::info!("signers: {:?}", keys.addresses());
+
+    let routes = route::routes(route::AppState {
+        keys,
+        hd,
+        policy: Arc::new(config.policy),
+        upstream,
+        audit,
+        idempotency,
+        redaction: config.log.redact,
+        kill_switch,
+        admin_token: config.admin.token,
+        probe_interval: Duration::from_secs(config.readiness.probe_interval_secs),
+    });
+    let app = Router::new()
+        .merge(routes)
+        .merge(metrics::routes(telemetry.registry.clone()))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(otel::request_span)
+                .on_failure(()),
+        );
+
+    // Every listener drains on the same shutdown signal.
+    let (shutdown_tx, shutdown_rx) = watch::channel(());
+    let mut servers = tokio::task::JoinSet::new();
+    for listener in &config.listeners {
+        let lisenter = TcpListener::bind(&listener.address).await?;
+        tracing::info!("listening on {}", listener.address);
+
+        let mut shutdown = shutdown_rx.clone();
+        let app = app.clone();
+        servers.spawn(async move {
+            axum::serve(
+                lisenter,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(async move {
+                let _ = shutdown.changed().await;
             })
-            .on_failure(()),
-    );
+            .await
+        });
+    }
+
+    tokio::select! {
+        _ = shutdown_signal() => {},
+        Some(result) = servers.join_next() => {
+            tracing::error!("listener stopped: {:?}", result);
+        }
+    }
+    let _ = shutdown_tx.send(());
+    while let Some(result) = servers.join_next().await {
+        if let Ok(Err(e)) = result {
+            tracing::error!("listener error: {}", e);
+        }
+    }
+
+    // Flush after the server drained, so spans of in-flight requests are kept.
+    telemetry.shutdown();
 
-    axum::serve(
-        lisenter,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .with_graceful_shutdown(shutdown_signal())
-    .await
-    .unwrap();
+    Ok(())
+}
 
+async fn run_command(command: config::Command) -> prelude::Result<()> {
+    match command {
+        config::Command::Audit(config::AuditCommand::Verify { path }) => {
+            let count = audit::verify(&path)?;
+            println!("audit chain ok: {count} records");
+        }
+        config::Command::Audit(config::AuditCommand::Export { path, from, to }) => {
+            audit::export(&path, from, to, std::io::stdout().lock())?;
+        }
+        config::Command::Config(config::ConfigCommand::Schema) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&config::ServiceConfig::schema())?
+            );
+        }
+        config::Command::Config(config::ConfigCommand::Check { path }) => {
+            let config = config::ServiceConfig::load(&path)?;
+            println!(
+                "config ok: {} listeners, {} signers",
+                config.listeners.len(),
+                config.signers.len()
+            );
+        }
+        config::Command::SplitSecret { threshold, shares } => {
+            let secret = Zeroizing::new(std::io::read_to_string(std::io::stdin())?);
+            for share in signer::split_secret(&secret, threshold, shares)? {
+                println!("{share}");
+            }
+        }
+        config::Command::Unseal(args) => {
+            let mut share = Zeroizing::new(String::new());
+            std::io::stdin().read_line(&mut share)?;
+            let body = serde_json::json!({ "share": share.trim() });
+            let response = admin_request(&args.url, &args.token, "/unseal", Some(body)).await?;
+            println!("{response}");
+        }
+        config::Command::Seal(args) => {
+            println!(
+                "{}",
+                admin_request(&args.url, &args.token, "/seal", None).await?
+            );
+        }
+        config::Command::Pause {
+            admin,
+            target,
+            reason,
+        } => {
+            let body = serde_json::json!({
+                "key": target.key,
+                "method": target.method,
+                "reason": reason,
+            });
+            let response = admin_request(&admin.url, &admin.token, "/admin/pause", Some(body));
+            println!("{}", response.await?);
+        }
+        config::Command::Resume { admin, target } => {
+            let body = serde_json::json!({ "key": target.key, "method": target.method });
+            let response = admin_request(&admin.url, &admin.token, "/admin/resume", Some(body));
+            println!("{}", response.await?);
+        }
+    }
     Ok(())
 }
 
+/// POST to an operator endpoint of a running service, returning its answer.
+async fn admin_request(
+    url: &str,
+    token: &secret::Secret,
+    path: &str,
+    body: Option<serde_json::Value>,
+) -> prelude::Result<String> {
+    let token = token.resolve(false)?;
+    let url = format!("{}{path}", url.trim_end_matches('/'));
+    let mut builder = reqwest::Client::new().post(url).bearer_auth(token.expose());
+    if let Some(body) = &body {
+        builder = builder.json(body);
+    }
+    let response = builder.send().await?;
+    let status = response.status();
+    let text = response.text().await?;
+    if !status.is_success() {
+        return Err(prelude::Error::AdminRequest(format!("{status}: {text}")));
+    }
+    Ok(text)
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
@@ -0,0 +1,205 @@
+use std::{sync::OnceLock, time::Instant};
+
+use axum::{Router, extract::State, http::header, response::IntoResponse, routing::get};
+use opentelemetry::{
+    KeyValue, global,
+    metrics::{Counter, Gauge, Histogram, UpDownCounter},
+};
+use prometheus::{Encoder, Registry, TextEncoder};
+use serde_json::Value;
+
+use crate::audit::AuditEvent;
+use crate::pause::PauseScope;
+
+/// Histogram buckets in seconds, from local keys to slow KMS round trips.
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Instruments recorded by the signing path. They are exported through the
+/// global meter provider, so they reach both `/metrics` and OTLP.
+pub struct Metrics {
+    sign_requests: Counter<u64>,
+    rejections: Counter<u64>,
+    backend_duration: Histogram<f64>,
+    backend_retries: Counter<u64>,
+    self_check_failures: Counter<u64>,
+    request_duration: Histogram<f64>,
+    in_flight: UpDownCounter<i64>,
+    paused: Gauge<i64>,
+}
+
+/// Must only be called after [`crate::otel::init`] installed the meter provider.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+    METRICS.get_or_init(|| {
+        let meter = global::meter("eth-signer");
+        Metrics {
+            sign_requests: meter
+                .u64_counter("eth_signer.sign.requests")
+                .with_description("Signing requests by method, key, chain and outcome")
+                .build(),
+            rejections: meter
+                .u64_counter("eth_signer.sign.rejections")
+                .with_description("Signing requests refused before reaching the backend")
+                .build(),
+            backend_duration: meter
+                .f64_histogram("eth_signer.backend.duration")
+                .with_unit("s")
+                .with_boundaries(LATENCY_BUCKETS.to_vec())
+                .with_description("Time spent in the signing backend")
+                .build(),
+            backend_retries: meter
+                .u64_counter("eth_signer.backend.retries")
+                .with_description("Remote backend calls repeated after a transient error")
+                .build(),
+            self_check_failures: meter
+                .u64_counter("eth_signer.sign.self_check_failures")
+                .with_description("Signatures withheld because they failed verification")
+                .build(),
+            request_duration: meter
+                .f64_histogram("eth_signer.request.duration")
+                .with_unit("s")
+                .with_boundaries(LATENCY_BUCKETS.to_vec())
+                .with_description("End-to-end JSON-RPC request latency")
+                .build(),
+            in_flight: meter
+                .i64_up_down_counter("eth_signer.requests.in_flight")
+                .with_description("JSON-RPC requests currently being handled")
+                .build(),
+            paused: meter
+                .i64_gauge("eth_signer.paused")
+                .with_description("1 while signing is paused for the scope, 0 once resumed")
+                .build(),
+        }
+    })
+}
+
+impl Metrics {
+    /// Count a signing decision, labelled from its audit event.
+    pub fn record_decision(&self, event: &AuditEvent) {
+        let label = |field: &str| match event.summary.get(field) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Null) | None => "unknown".to_string(),
+            Some(v) => v.to_string(),
+        };
+        self.sign_requests.add(
+            1,
+            &[
+                KeyValue::new("method", event.method.clone()),
+                KeyValue::new("key", label("from")),
+                KeyValue::new("chain", label("chain_id")),
+                KeyValue::new("outcome", event.decision.as_str()),
+            ],
+        );
+    }
+
+    pub fn record_rejection(&self, method: &str, reason: &'static str) {
+        self.rejections.add(
+            1,
+            &[
+                KeyValue::new("method", method.to_string()),
+                KeyValue::new("reason", reason),
+            ],
+        );
+    }
+
+    pub fn record_backend(&self, backend: &'static str, started: Instant, ok: bool) {
+        self.backend_duration.record(
+            started.elapsed().as_secs_f64(),
+            &[
+                KeyValue::new("backend", backend),
+                KeyValue::new("outcome", if ok { "ok" } else { "error" }),
+            ],
+        );
+    }
+
+    pub fn record_retry(&self, backend: &'static str, operation: &'static str) {
+        self.backend_retries.add(
+            1,
+            &[
+                KeyValue::new("backend", backend),
+                KeyValue::new("operation", operation),
+            ],
+        );
+    }
+
+    pub fn record_pause(&self, scope: &PauseScope, paused: bool) {
+        let (kind, target) = match scope {
+            PauseScope::All => ("all", String::new()),
+            PauseScope::Key(address) => ("key", address.to_string()),
+            PauseScope::Method(method) => ("method", method.clone()),
+        };
+        self.paused.record(
+            i64::from(paused),
+            &[
+                KeyValue::new("scope", kind),
+                KeyValue::new("target", target),
+            ],
+        );
+    }
+
+    pub fn record_self_check_failure(&self, backend: &'static str) {
+        self.self_check_failures
+            .add(1, &[KeyValue::new("backend", backend)]);
+    }
+
+    /// Track a request from now until the returned guard is dropped.
+    pub fn start_request(&self, method: &'static str) -> RequestGuard<'_> {
+        self.in_flight.add(1, &[KeyValue::new("method", method)]);
+        RequestGuard {
+            metrics: self,
+            method,
+            started: Instant::now(),
+            success: false,
+        }
+    }
+}
+
+pub struct RequestGuard<'a> {
+    metrics: &'a Metrics,
+    method: &'static str,
+    started: Instant,
+    success: bool,
+}
+
+impl RequestGuard<'_> {
+    pub fn success(&mut self, success: bool) {
+        self.success = success;
+    }
+}
+
+impl Drop for RequestGuard<'_> {
+    fn drop(&mut self) {
+        let method = KeyValue::new("method", self.method);
+        self.metrics
+            .in_flight
+            .add(-1, std::slice::from_ref(&method));
+        self.metrics.request_duration.record(
+            self.started.elapsed().as_secs_f64(),
+            &[
+                method,
+                KeyValue::new("outcome", if self.success { "ok" } else { "error" }),
+            ],
+        );
+    }
+}
+
+async fn scrape(registry: State<Registry>) -> impl IntoResponse {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    if let Err(e) = encoder.encode(&registry.gather(), &mut buffer) {
+        tracing::error!("encode metrics error: {}", e);
+    }
+    (
+        [(header::CONTENT_TYPE, encoder.format_type().to_string())],
+        buffer,
+    )
+}
+
+pub fn routes(registry: Registry) -> Router {
+    Router::new()
+        .route("/metrics", get(scrape))
+        .with_state(registry)
+}
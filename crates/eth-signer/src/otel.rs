@@ -1,57 +1,253 @@
-use std::sync::OnceLock;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
 
-use opentelemetry::global;
-use opentelemetry_otlp::{LogExporter, MetricExporter, Protocol, SpanExporter, WithExportConfig};
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{HeaderMap, HeaderName, HeaderValue},
+};
+use clap::ValueEnum;
+use opentelemetry::{Context, KeyValue, global, trace::TracerProvider};
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_otlp::{
+    LogExporter, MetricExporter, Protocol, SpanExporter, WithExportConfig, WithHttpConfig,
+    WithTonicConfig, tonic_types::metadata::MetadataMap,
+};
 use opentelemetry_sdk::{
-    Resource, logs::SdkLoggerProvider, metrics::SdkMeterProvider, trace::SdkTracerProvider,
+    Resource,
+    logs::SdkLoggerProvider,
+    metrics::SdkMeterProvider,
+    propagation::TraceContextPropagator,
+    trace::{Sampler, SdkTracerProvider},
 };
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt, util::SubscriberInitExt};
 
-fn get_resource() -> Resource {
-    static RESOURCE: OnceLock<Resource> = OnceLock::new();
+/// Telemetry section of the service configuration. Values follow the
+/// standard `OTEL_*` environment variables, which override them.
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetryConfig {
+    /// OTLP collector endpoint. Nothing is exported over OTLP when unset.
+    pub endpoint: Option<String>,
+    /// Export protocol for all signals.
+    pub protocol: OtlpProtocol,
+    pub traces_protocol: Option<OtlpProtocol>,
+    pub metrics_protocol: Option<OtlpProtocol>,
+    pub logs_protocol: Option<OtlpProtocol>,
+    /// Headers sent with every export.
+    pub headers: BTreeMap<String, String>,
+    /// Fraction of root traces sampled; child spans follow their parent.
+    pub sampling_ratio: f64,
+    /// `service.name` resource attribute.
+    pub service_name: String,
+    pub resource_attributes: BTreeMap<String, String>,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            protocol: OtlpProtocol::default(),
+            traces_protocol: None,
+            metrics_protocol: None,
+            logs_protocol: None,
+            headers: BTreeMap::new(),
+            sampling_ratio: 1.0,
+            service_name: String::from("eth-signer"),
+            resource_attributes: BTreeMap::new(),
+        }
+    }
+}
+
+impl fmt::Debug for TelemetryConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Export headers commonly carry collector credentials.
+        f.debug_struct("TelemetryConfig")
+            .field("endpoint", &self.endpoint)
+            .field("protocol", &self.protocol)
+            .field("traces_protocol", &self.traces_protocol)
+            .field("metrics_protocol", &self.metrics_protocol)
+            .field("logs_protocol", &self.logs_protocol)
+            .field("headers", &self.headers.keys().collect::<Vec<_>>())
+            .field("sampling_ratio", &self.sampling_ratio)
+            .field("service_name", &self.service_name)
+            .field("resource_attributes", &self.resource_attributes)
+            .finish()
+    }
+}
+
+#[derive(
+    ValueEnum, Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Default,
+)]
+pub enum OtlpProtocol {
+    #[value(name = "grpc")]
+    #[serde(rename = "grpc")]
+    Grpc,
+    #[default]
+    #[value(name = "http/protobuf")]
+    #[serde(rename = "http/protobuf")]
+    HttpProtobuf,
+    #[value(name = "http/json")]
+    #[serde(rename = "http/json")]
+    HttpJson,
+}
+
+/// Installed telemetry providers. OTLP providers are only present when an
+/// exporter endpoint is configured.
+pub struct Telemetry {
+    pub registry: prometheus::Registry,
+    tracer_provider: Option<SdkTracerProvider>,
+    meter_provider: SdkMeterProvider,
+    logger_provider: Option<SdkLoggerProvider>,
+}
 
-    RESOURCE
-        .get_or_init(|| Resource::builder().with_service_name("eth-signer").build())
-        .clone()
+impl Telemetry {
+    /// Flush pending telemetry and stop the exporters.
+    pub fn shutdown(&self) {
+        if let Some(provider) = &self.tracer_provider
+            && let Err(e) = provider.shutdown()
+        {
+            tracing::warn!("shutdown tracer provider error: {}", e);
+        }
+        if let Err(e) = self.meter_provider.shutdown() {
+            tracing::warn!("shutdown meter provider error: {}", e);
+        }
+        if let Some(provider) = &self.logger_provider
+            && let Err(e) = provider.shutdown()
+        {
+            // The console layer is still installed, only OTLP export is gone.
+            tracing::warn!("shutdown logger provider error: {}", e);
+        }
+    }
 }
 
-fn init_logs() -> SdkLoggerProvider {
-    let exporter = LogExporter::builder()
-        .with_http()
-        .with_protocol(Protocol::HttpBinary)
+fn get_resource(opts: &TelemetryConfig) -> Resource {
+    Resource::builder()
+        .with_service_name(opts.service_name.clone())
+        .with_attributes(
+            opts.resource_attributes
+                .iter()
+                .map(|(k, v)| KeyValue::new(k.clone(), v.clone())),
+        )
         .build()
-        .expect("Failed to create log exporter");
+}
+
+/// Build an OTLP exporter for `$signal` with the configured protocol. HTTP
+/// exporters get the per-signal path appended to the base endpoint, as the
+/// OTLP specification requires for `OTEL_EXPORTER_OTLP_ENDPOINT`.
+macro_rules! otlp_exporter {
+    ($builder:expr, $opts:expr, $endpoint:expr, $protocol:expr, $signal:literal) => {
+        match $protocol {
+            OtlpProtocol::Grpc => $builder
+                .with_tonic()
+                .with_endpoint($endpoint)
+                .with_metadata(MetadataMap::from_headers(header_map(&$opts.headers)))
+                .build(),
+            protocol => $builder
+                .with_http()
+                .with_endpoint(format!(
+                    "{}/v1/{}",
+                    $endpoint.trim_end_matches('/'),
+                    $signal
+                ))
+                .with_protocol(match protocol {
+                    OtlpProtocol::HttpJson => Protocol::HttpJson,
+                    _ => Protocol::HttpBinary,
+                })
+                .with_headers(
+                    $opts
+                        .headers
+                        .iter()
+                        .map(|(k, v)| (k.clone(), v.clone()))
+                        .collect::<HashMap<_, _>>(),
+                )
+                .build(),
+        }
+    };
+}
+
+fn header_map(headers: &BTreeMap<String, String>) -> HeaderMap {
+    headers
+        .iter()
+        .filter_map(|(k, v)| {
+            Some((
+                HeaderName::try_from(k.as_str()).ok()?,
+                HeaderValue::try_from(v.as_str()).ok()?,
+            ))
+        })
+        .collect()
+}
+
+fn init_logs(opts: &TelemetryConfig, endpoint: &str, resource: Resource) -> SdkLoggerProvider {
+    let exporter = otlp_exporter!(
+        LogExporter::builder(),
+        opts,
+        endpoint,
+        opts.logs_protocol.unwrap_or(opts.protocol),
+        "logs"
+    )
+    .expect("Failed to create log exporter");
 
     SdkLoggerProvider::builder()
         .with_batch_exporter(exporter)
-        .with_resource(get_resource())
+        .with_resource(resource)
         .build()
 }
 
-pub(super) fn init_metrics() -> SdkMeterProvider {
-    let exporter = MetricExporter::builder()
-        .with_http()
-        .with_protocol(Protocol::HttpBinary)
+pub(super) fn init_metrics(
+    opts: &TelemetryConfig,
+    registry: &prometheus::Registry,
+    resource: Resource,
+) -> SdkMeterProvider {
+    let prometheus_exporter = opentelemetry_prometheus::exporter()
+        .with_registry(registry.clone())
         .build()
-        .expect("Failed to create metric exporter");
+        .expect("Failed to create prometheus exporter");
 
-    SdkMeterProvider::builder()
-        .with_periodic_exporter(exporter)
-        .with_resource(get_resource())
-        .build()
+    let builder = SdkMeterProvider::builder()
+        .with_reader(prometheus_exporter)
+        .with_resource(resource);
+
+    let Some(endpoint) = &opts.endpoint else {
+        return builder.build();
+    };
+
+    let exporter = otlp_exporter!(
+        MetricExporter::builder(),
+        opts,
+        endpoint,
+        opts.metrics_protocol.unwrap_or(opts.protocol),
+        "metrics"
+    )
+    .expect("Failed to create metric exporter");
+
+    builder.with_periodic_exporter(exporter).build()
 }
 
-pub(super) fn init_traces() -> SdkTracerProvider {
-    let exporter = SpanExporter::builder()
-        .with_http()
-        // .with_endpoint(endpoint)
-        .with_protocol(Protocol::HttpJson)
-        .build()
-        .expect("failed to create span exporter");
+pub(super) fn init_traces(
+    opts: &TelemetryConfig,
+    endpoint: &str,
+    resource: Resource,
+) -> SdkTracerProvider {
+    let exporter = otlp_exporter!(
+        SpanExporter::builder(),
+        opts,
+        endpoint,
+        opts.traces_protocol.unwrap_or(opts.protocol),
+        "traces"
+    )
+    .expect("failed to create span exporter");
 
     SdkTracerProvider::builder()
         .with_batch_exporter(exporter)
-        .with_resource(get_resource())
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            opts.sampling_ratio,
+        ))))
+        .with_resource(resource)
         .build()
 }
 
@@ -66,22 +262,64 @@ pub fn get_env_filter() -> EnvFilter {
     }
 }
 
-pub fn init(debug: bool) {
-    let tracer_provider = init_traces();
-    let meter_provider = init_metrics();
-    let logger_provider = init_logs();
-
-    let env_filter = get_env_filter();
-
-    let filter_otel = env_filter
-        .clone()
+fn get_otel_filter() -> EnvFilter {
+    get_env_filter()
         .add_directive("hyper=off".parse().unwrap())
         .add_directive("tonic=off".parse().unwrap())
         .add_directive("h2=off".parse().unwrap())
-        .add_directive("reqwest=off".parse().unwrap());
-    let otel_layer =
-        opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge::new(&logger_provider)
-            .with_filter(filter_otel);
+        .add_directive("reqwest=off".parse().unwrap())
+}
+
+/// Root span of an HTTP request, continuing the caller's trace when it sent
+/// `traceparent`. It and the `rpc` span below it are `info` spans: a disabled
+/// span would drop the parent context, and `sign` would start a new trace.
+pub fn request_span(req: &Request) -> tracing::Span {
+    let method = req.method();
+    let uri = req.uri();
+    // axum automatically adds this extension.
+    let matched_path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched_path| matched_path.as_str());
+    let span = tracing::info_span!("request", %method, %uri, matched_path);
+    let _ = span.set_parent(extract_context(req.headers()));
+    span
+}
+
+pub fn extract_context(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+/// Trace context headers of the current span, for outbound calls.
+pub fn current_context_headers() -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+    let cx = tracing::Span::current().context();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&cx, &mut headers));
+    headers
+}
+
+/// Install the global telemetry providers. The Prometheus registry is always
+/// available; OTLP export is only enabled when an endpoint is configured.
+pub fn init(debug: bool, opts: &TelemetryConfig) -> Telemetry {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let registry = prometheus::Registry::new();
+    let resource = get_resource(opts);
+    let endpoint = opts.endpoint.as_deref();
+
+    let tracer_provider = endpoint.map(|e| init_traces(opts, e, resource.clone()));
+    let meter_provider = init_metrics(opts, &registry, resource.clone());
+    let logger_provider = endpoint.map(|e| init_logs(opts, e, resource.clone()));
+
+    let otel_log_layer = logger_provider.as_ref().map(|provider| {
+        opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge::new(provider)
+            .with_filter(get_otel_filter())
+    });
+    let otel_trace_layer = tracer_provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer("eth-signer"))
+            .with_filter(get_otel_filter())
+    });
 
     let fmt_layer = match debug {
         true => tracing_subscriber::fmt::layer()
@@ -97,13 +335,65 @@ pub fn init(debug: bool) {
             .with_line_number(true)
             .boxed(),
     }
-    .with_filter(env_filter);
+    .with_filter(get_env_filter());
 
     tracing_subscriber::registry()
-        .with(otel_layer)
+        .with(otel_log_layer)
+        .with(otel_trace_layer)
         .with(fmt_layer)
         .init();
 
-    global::set_tracer_provider(tracer_provider);
-    global::set_meter_provider(meter_provider);
+    if let Some(provider) = &tracer_provider {
+        global::set_tracer_provider(provider.clone());
+    }
+    global::set_meter_provider(meter_provider.clone());
+
+    Telemetry {
+        registry,
+        tracer_provider,
+        meter_provider,
+        logger_provider,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use opentelemetry::trace::TraceId;
+    use opentelemetry_sdk::trace::InMemorySpanExporter;
+
+    #[test]
+    fn sign_span_continues_caller_trace() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        // The release default filter.
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_opentelemetry::layer()
+                .with_tracer(provider.tracer("test"))
+                .with_filter(EnvFilter::new("info")),
+        );
+
+        let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+        let request = Request::builder()
+            .uri("/")
+            .header("traceparent", format!("00-{trace_id}-00f067aa0ba902b7-01"))
+            .body(Body::empty())
+            .unwrap();
+        tracing::subscriber::with_default(subscriber, || {
+            let _request = request_span(&request).entered();
+            let _rpc = tracing::info_span!("rpc").entered();
+            tracing::info_span!("sign").in_scope(|| {});
+        });
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let sign = spans.iter().find(|span| span.name == "sign").unwrap();
+        assert_eq!(
+            sign.span_context.trace_id(),
+            TraceId::from_hex(trace_id).unwrap()
+        );
+    }
 }
@@ -0,0 +1,213 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use alloy::primitives::Address;
+use serde::{Deserialize, Serialize};
+
+use crate::metrics::metrics;
+use crate::prelude::*;
+
+/// What a pause stops.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PauseScope {
+    All,
+    Key(Address),
+    Method(String),
+}
+
+impl fmt::Display for PauseScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PauseScope::All => f.write_str("all keys"),
+            PauseScope::Key(address) => write!(f, "key {address}"),
+            PauseScope::Method(method) => write!(f, "method {method}"),
+        }
+    }
+}
+
+/// Who paused and why, as given to the admin API.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Pause {
+    pub reason: String,
+    /// Unix time in seconds.
+    pub since: u64,
+}
+
+/// Active pauses, as persisted and reported by `/admin/pause`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct PauseState {
+    pub all: Option<Pause>,
+    pub keys: BTreeMap<Address, Pause>,
+    pub methods: BTreeMap<String, Pause>,
+}
+
+/// Emergency stop for signing. Pauses hold until they are resumed, across
+/// restarts when a state file is configured.
+pub struct KillSwitch {
+    path: Option<PathBuf>,
+    state: Mutex<PauseState>,
+}
+
+impl KillSwitch {
+    /// Start with the pauses saved at `path`, if any.
+    pub fn open(path: Option<impl AsRef<Path>>) -> Result<Self> {
+        let path = path.map(|p| p.as_ref().to_path_buf());
+        let state = match &path {
+            Some(path) => match std::fs::read(path) {
+                Ok(bytes) => serde_json::from_slice(&bytes)?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => PauseState::default(),
+                Err(e) => return Err(e.into()),
+            },
+            None => PauseState::default(),
+        };
+        for scope in state.scopes() {
+            tracing::warn!("signing is paused for {}", scope);
+            metrics().record_pause(&scope, true);
+        }
+        Ok(Self {
+            path,
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn state(&self) -> PauseState {
+        self.state.lock().expect("lock poisoned").clone()
+    }
+
+    /// Refuse `method` requests for `from` while a pause covers them.
+    pub fn check(&self, method: &str, from: Option<Address>) -> Result<()> {
+        let state = self.state.lock().expect("lock poisoned");
+        let pause = state
+            .all
+            .as_ref()
+            .map(|pause| (PauseScope::All, pause))
+            .or_else(|| {
+                let address = from?;
+                let pause = state.keys.get(&address)?;
+                Some((PauseScope::Key(address), pause))
+            })
+            .or_else(|| {
+                let pause = state.methods.get(method)?;
+                Some((PauseScope::Method(method.to_string()), pause))
+            });
+        match pause {
+            Some((scope, pause)) if pause.reason.is_empty() => {
+                Err(Error::Paused(scope.to_string()))
+            }
+            Some((scope, pause)) => Err(Error::Paused(format!("{scope}: {}", pause.reason))),
+            None => Ok(()),
+        }
+    }
+
+    /// Stop signing for `scope`. The pause applies at once, even when it
+    /// cannot be saved.
+    pub fn pause(&self, scope: PauseScope, reason: String) -> Result<PauseState> {
+        let mut state = self.state.lock().expect("lock poisoned");
+        let since = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let pause = Pause { reason, since };
+        match &scope {
+            PauseScope::All => state.all = Some(pause),
+            PauseScope::Key(address) => {
+                state.keys.insert(*address, pause);
+            }
+            PauseScope::Method(method) => {
+                state.methods.insert(method.clone(), pause);
+            }
+        }
+        metrics().record_pause(&scope, true);
+        self.save(&state)?;
+        Ok(state.clone())
+    }
+
+    /// Lift the pause of `scope`. Nothing changes unless it can be saved, so
+    /// a restart cannot bring back a pause that was lifted.
+    pub fn resume(&self, scope: &PauseScope) -> Result<PauseState> {
+        let mut state = self.state.lock().expect("lock poisoned");
+        let mut resumed = state.clone();
+        match scope {
+            PauseScope::All => resumed.all = None,
+            PauseScope::Key(address) => {
+                resumed.keys.remove(address);
+            }
+            PauseScope::Method(method) => {
+                resumed.methods.remove(method);
+            }
+        }
+        self.save(&resumed)?;
+        metrics().record_pause(scope, false);
+        *state = resumed;
+        Ok(state.clone())
+    }
+
+    /// Replace the state file through a rename, so a crash leaves either
+    /// the old or the new state.
+    fn save(&self, state: &PauseState) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(state)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+impl PauseState {
+    pub fn scopes(&self) -> Vec<PauseScope> {
+        self.all
+            .iter()
+            .map(|_| PauseScope::All)
+            .chain(self.keys.keys().copied().map(PauseScope::Key))
+            .chain(self.methods.keys().cloned().map(PauseScope::Method))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pause_persists_until_resumed() {
+        let dir = std::env::temp_dir().join(format!("eth-signer-pause-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pause.json");
+        let key = Address::repeat_byte(1);
+
+        let switch = KillSwitch::open(Some(&path)).unwrap();
+        switch
+            .pause(PauseScope::Key(key), "incident".to_string())
+            .unwrap();
+        switch
+            .pause(
+                PauseScope::Method("hd_signTransaction".to_string()),
+                String::new(),
+            )
+            .unwrap();
+        assert!(switch.check("eth_signTransaction", Some(key)).is_err());
+        assert!(switch.check("eth_signTransaction", None).is_ok());
+        assert!(switch.check("hd_signTransaction", None).is_err());
+
+        let reopened = KillSwitch::open(Some(&path)).unwrap();
+        assert_eq!(reopened.state(), switch.state());
+        reopened.pause(PauseScope::All, String::new()).unwrap();
+        assert!(reopened.check("eth_signTransaction", None).is_err());
+        for scope in reopened.state().scopes() {
+            reopened.resume(&scope).unwrap();
+        }
+        assert_eq!(
+            KillSwitch::open(Some(&path)).unwrap().state(),
+            PauseState::default()
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
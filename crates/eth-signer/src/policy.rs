@@ -0,0 +1,252 @@
+use alloy::{
+    eips::eip7702::Authorization,
+    primitives::{Address, TxKind, U256},
+    rpc::types::TransactionRequest,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// Restrictions checked before a transaction or authorization is signed. An
+/// empty policy allows everything except EIP-7702 delegations.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    /// Chain ids that may be signed for. Empty allows any chain.
+    pub allowed_chains: Vec<u64>,
+    /// Per-chain limits.
+    pub chains: Vec<ChainPolicy>,
+    /// Contracts an EIP-7702 authorization may delegate to, whether signed on
+    /// its own or carried in a transaction's `authorizationList`. A delegate
+    /// controls the account, so none is allowed unless listed. Clearing a
+    /// delegation with the zero address is always allowed.
+    #[schemars(with = "Vec<String>")]
+    pub allowed_delegates: Vec<Address>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ChainPolicy {
+    pub chain_id: u64,
+    /// Largest `value` in wei.
+    #[schemars(with = "Option<String>")]
+    #[serde(default)]
+    pub max_value: Option<U256>,
+    /// Largest `maxFeePerGas` or `gasPrice` in wei.
+    #[serde(default)]
+    pub max_fee_per_gas: Option<u128>,
+    /// Largest `maxFeePerBlobGas` in wei.
+    #[serde(default)]
+    pub max_fee_per_blob_gas: Option<u128>,
+    /// Recipients that may be called. Empty allows any recipient.
+    #[schemars(with = "Vec<String>")]
+    #[serde(default)]
+    pub allowed_to: Vec<Address>,
+}
+
+impl Policy {
+    pub fn check(&self, request: &TransactionRequest) -> Result<()> {
+        for authorization in request.authorization_list.iter().flatten() {
+            self.check_delegate(authorization.address)?;
+        }
+        if self.allowed_chains.is_empty() && self.chains.is_empty() {
+            return Ok(());
+        }
+        let chain_id = request
+            .chain_id
+            .ok_or_else(|| Error::PolicyViolation("chain id is required".to_string()))?;
+        if !self.allowed_chains.is_empty() && !self.allowed_chains.contains(&chain_id) {
+            return Err(Error::PolicyViolation(format!(
+                "chain {chain_id} is not allowed"
+            )));
+        }
+        match self.chains.iter().find(|c| c.chain_id == chain_id) {
+            Some(chain) => chain.check(request),
+            None => Ok(()),
+        }
+    }
+
+    /// Check an authorization to be signed. One for chain 0 is valid on every
+    /// chain, so it is refused when chains are restricted.
+    pub fn check_authorization(&self, authorization: &Authorization) -> Result<()> {
+        self.check_delegate(authorization.address)?;
+        if self.allowed_chains.is_empty() {
+            return Ok(());
+        }
+        let chain_id = authorization.chain_id;
+        if chain_id.is_zero() {
+            return Err(Error::PolicyViolation(
+                "authorization for every chain is not allowed".to_string(),
+            ));
+        }
+        if !self
+            .allowed_chains
+            .iter()
+            .any(|allowed| U256::from(*allowed) == chain_id)
+        {
+            return Err(Error::PolicyViolation(format!(
+                "chain {chain_id} is not allowed"
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_delegate(&self, delegate: Address) -> Result<()> {
+        if delegate.is_zero() || self.allowed_delegates.contains(&delegate) {
+            return Ok(());
+        }
+        Err(Error::PolicyViolation(format!(
+            "delegate {delegate} is not allowed"
+        )))
+    }
+}
+
+impl ChainPolicy {
+    fn check(&self, request: &TransactionRequest) -> Result<()> {
+        if let Some(max) = self.max_value
+            && request.value.unwrap_or_default() > max
+        {
+            return Err(Error::PolicyViolation(format!(
+                "value exceeds {max} on chain {}",
+                self.chain_id
+            )));
+        }
+        if let Some(max) = self.max_fee_per_gas {
+            let fee = request.max_fee_per_gas.or(request.gas_price);
+            if fee.is_some_and(|fee| fee > max) {
+                return Err(Error::PolicyViolation(format!(
+                    "fee per gas exceeds {max} on chain {}",
+                    self.chain_id
+                )));
+            }
+        }
+        if let Some(max) = self.max_fee_per_blob_gas
+            && request.max_fee_per_blob_gas.is_some_and(|fee| fee > max)
+        {
+            return Err(Error::PolicyViolation(format!(
+                "fee per blob gas exceeds {max} on chain {}",
+                self.chain_id
+            )));
+        }
+        if !self.allowed_to.is_empty() {
+            match request.to {
+                Some(TxKind::Call(to)) if self.allowed_to.contains(&to) => {}
+                Some(TxKind::Call(to)) => {
+                    return Err(Error::PolicyViolation(format!(
+                        "recipient {to} is not allowed on chain {}",
+                        self.chain_id
+                    )));
+                }
+                _ => {
+                    return Err(Error::PolicyViolation(
+                        "contract creation is not allowed".to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::{eips::eip7702::SignedAuthorization, primitives::address};
+
+    #[test]
+    fn check_chain_limits() {
+        let router = address!("0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D");
+        let policy = Policy {
+            allowed_chains: vec![1],
+            chains: vec![ChainPolicy {
+                chain_id: 1,
+                max_value: Some(U256::from(100)),
+                max_fee_per_gas: None,
+                max_fee_per_blob_gas: Some(10),
+                allowed_to: vec![router],
+            }],
+            allowed_delegates: Vec::new(),
+        };
+        let request = TransactionRequest::default()
+            .to(router)
+            .value(U256::from(100));
+
+        assert!(policy.check(&request).is_err(), "chain id is required");
+        let request = TransactionRequest {
+            chain_id: Some(1),
+            ..request
+        };
+        assert!(policy.check(&request).is_ok());
+        assert!(
+            policy
+                .check(&request.clone().value(U256::from(101)))
+                .is_err()
+        );
+        assert!(policy.check(&request.clone().to(Address::ZERO)).is_err());
+        let blob_fee = TransactionRequest {
+            max_fee_per_blob_gas: Some(11),
+            ..request.clone()
+        };
+        assert!(policy.check(&blob_fee).is_err());
+        let other_chain = TransactionRequest {
+            chain_id: Some(5),
+            ..request
+        };
+        assert!(policy.check(&other_chain).is_err());
+    }
+
+    #[test]
+    fn check_delegates() {
+        let delegate = address!("0x63c0c19a282a1B52b07dD5a65b58948A07DAE32B");
+        let authorization = |chain_id: u64, address: Address| Authorization {
+            chain_id: U256::from(chain_id),
+            address,
+            nonce: 0,
+        };
+        let mut policy = Policy::default();
+        assert!(
+            policy
+                .check_authorization(&authorization(1, delegate))
+                .is_err()
+        );
+        assert!(
+            policy
+                .check_authorization(&authorization(1, Address::ZERO))
+                .is_ok()
+        );
+
+        policy.allowed_delegates = vec![delegate];
+        assert!(
+            policy
+                .check_authorization(&authorization(0, delegate))
+                .is_ok()
+        );
+        policy.allowed_chains = vec![1];
+        assert!(
+            policy
+                .check_authorization(&authorization(1, delegate))
+                .is_ok()
+        );
+        assert!(
+            policy
+                .check_authorization(&authorization(0, delegate))
+                .is_err()
+        );
+        assert!(
+            policy
+                .check_authorization(&authorization(5, delegate))
+                .is_err()
+        );
+
+        let signed = |address| {
+            SignedAuthorization::new_unchecked(authorization(1, address), 0, U256::ZERO, U256::ZERO)
+        };
+        let request = TransactionRequest {
+            chain_id: Some(1),
+            authorization_list: Some(vec![signed(delegate), signed(Address::repeat_byte(9))]),
+            ..Default::default()
+        };
+        assert!(policy.check(&request).is_err());
+    }
+}
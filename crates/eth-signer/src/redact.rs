@@ -0,0 +1,52 @@
+use alloy::primitives::keccak256;
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// How much of calldata and raw transactions is written to logs and spans.
+#[derive(
+    ValueEnum, Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Default,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Redaction {
+    /// Only the keccak256 hash and length.
+    #[default]
+    Hash,
+    /// The leading bytes, typically the function selector, and length.
+    Truncate,
+    /// The complete data.
+    Full,
+}
+
+/// Bytes kept by [`Redaction::Truncate`], enough for a function selector.
+const TRUNCATE_LEN: usize = 4;
+
+impl Redaction {
+    pub fn apply(&self, data: &[u8]) -> String {
+        match self {
+            Redaction::Hash if data.is_empty() => String::from("0x"),
+            Redaction::Hash => format!("keccak256:{} ({} bytes)", keccak256(data), data.len()),
+            Redaction::Truncate if data.len() <= TRUNCATE_LEN => alloy::hex::encode_prefixed(data),
+            Redaction::Truncate => format!(
+                "{}... ({} bytes)",
+                alloy::hex::encode_prefixed(&data[..TRUNCATE_LEN]),
+                data.len()
+            ),
+            Redaction::Full => alloy::hex::encode_prefixed(data),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_calldata() {
+        let data = [0xa9, 0x05, 0x9c, 0xbb, 0x00, 0x01];
+        assert_eq!(Redaction::Full.apply(&data), "0xa9059cbb0001");
+        assert_eq!(Redaction::Truncate.apply(&data), "0xa9059cbb... (6 bytes)");
+        assert!(Redaction::Hash.apply(&data).ends_with("(6 bytes)"));
+        assert!(!Redaction::Hash.apply(&data).contains("a9059cbb"));
+    }
+}
@@ -1,35 +1,311 @@
-use std::hash::{DefaultHasher, Hash};
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use crate::audit::{self, AuditEvent, AuditLog, Decision, RecordKind};
+use crate::blob;
+use crate::idempotency::{IdempotencyCache, Reservation};
+use crate::metrics::metrics;
+use crate::pause::{KillSwitch, PauseScope, PauseState};
+use crate::policy::Policy;
 use crate::prelude::*;
-use crate::signer::SignerConfig;
+use crate::redact::Redaction;
+use crate::secret::Secret;
+use crate::signer::{ChildKey, HdKeys, KeyHealth, KeyRing, SealStatus};
+use crate::upstream::Upstream;
+use crate::verify;
 use alloy::{
-    eips::eip2718::Encodable2718,
-    network::TransactionBuilder,
-    primitives::TxKind,
+    consensus::TxEnvelope,
+    eips::{
+        eip2718::{Decodable2718, Encodable2718},
+        eip7702::Authorization,
+    },
+    network::{EthereumWallet, TransactionBuilder},
+    primitives::{Address, TxKind, keccak256},
     rpc::{
-        json_rpc::{Request as JrpcRequest, Response as JrpcResponse, ResponsePayload},
+        json_rpc::{Id, Request as JrpcRequest, Response as JrpcResponse, ResponsePayload},
         types::{TransactionInput, TransactionRequest},
     },
 };
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use tracing::info;
+use tracing::{Instrument, info};
 
 use axum::{
     Router,
     body::Bytes,
-    extract::{Json, State},
+    extract::{ConnectInfo, DefaultBodyLimit, Json, Query, State},
+    http::{HeaderMap, StatusCode, header::AUTHORIZATION},
     response::IntoResponse,
     routing::{get, post},
 };
 
 const SIGN_TX_METHOD: &str = "eth_signTransaction";
+const ACCOUNTS_METHOD: &str = "eth_accounts";
+const HD_SIGN_TX_METHOD: &str = "hd_signTransaction";
+const SIGN_AUTHORIZATION_METHOD: &str = "eth_signAuthorization";
 const HEALTH_STATUS: &str = "health_status";
 
-type Params = Vec<Box<serde_json::value::RawValue>>;
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+pub type Params = Vec<Box<serde_json::value::RawValue>>;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub keys: Arc<KeyRing>,
+    pub hd: Option<Arc<HdKeys>>,
+    pub policy: Arc<Policy>,
+    pub upstream: Option<Arc<Upstream>>,
+    pub audit: Option<Arc<AuditLog>>,
+    pub idempotency: Option<Arc<IdempotencyCache>>,
+    pub redaction: Redaction,
+    pub kill_switch: Arc<KillSwitch>,
+    /// Bearer token of the `/admin` routes, which are disabled without one.
+    pub admin_token: Option<Secret>,
+    /// How long a key's readiness probe is reused.
+    pub probe_interval: Duration,
+}
+
+/// Per-request information about the caller.
+pub struct RequestContext {
+    pub caller: SocketAddr,
+    pub idempotency_key: Option<String>,
+}
+
+impl AppState {
+    /// Record a signing decision in metrics and the audit log.
+    fn record(&self, event: AuditEvent) -> Result<()> {
+        metrics().record_decision(&event);
+        if let Some(log) = &self.audit {
+            log.append(event)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct Readiness {
+    ready: bool,
+    sealed: bool,
+    /// Active pauses. A paused service stays ready so clients get the
+    /// paused error.
+    paused: Vec<String>,
+    keys: Vec<KeyHealth>,
+}
+
+/// Ready while unsealed and every key signs its probe.
+async fn readiness(state: &AppState) -> Readiness {
+    let sealed = state.keys.is_sealed();
+    let keys = state.keys.health(state.probe_interval).await;
+    let paused = state.kill_switch.state().scopes();
+    Readiness {
+        ready: !sealed && keys.iter().all(|key| key.ok),
+        sealed,
+        paused: paused.iter().map(ToString::to_string).collect(),
+        keys,
+    }
+}
+
+async fn ready(state: State<AppState>) -> (StatusCode, Json<Readiness>) {
+    let readiness = readiness(&state).await;
+    let status = match readiness.ready {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status, Json(readiness))
+}
+
+async fn pub_key(state: State<AppState>) -> std::result::Result<String, (StatusCode, String)> {
+    match state.keys.default_address() {
+        Some(address) => Ok(address.to_string()),
+        None => Err((StatusCode::SERVICE_UNAVAILABLE, Error::Sealed.to_string())),
+    }
+}
+
+#[derive(Deserialize)]
+struct UnsealRequest {
+    /// Hex encoded Shamir share.
+    share: String,
+}
+
+type SealResponse = std::result::Result<Json<SealStatus>, (StatusCode, String)>;
+
+/// Unsealing progress of the sealed signer.
+async fn seal_status(state: State<AppState>) -> SealResponse {
+    match state.keys.seal_status() {
+        Some(status) => Ok(Json(status)),
+        None => Err(no_sealed_signer()),
+    }
+}
+
+/// Submit one operator's share of the sealed signer's secret.
+async fn unseal(
+    state: State<AppState>,
+    ConnectInfo(caller): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(request): Json<UnsealRequest>,
+) -> SealResponse {
+    authorize_seal(&state, &headers)?;
+    match state.keys.unseal(&request.share) {
+        Ok(status) => {
+            info!(%caller, progress = status.progress, sealed = status.sealed, "unseal share");
+            Ok(Json(status))
+        }
+        Err(e @ Error::InvalidShare(_)) => {
+            tracing::warn!(%caller, "unseal share refused: {}", e);
+            Err((StatusCode::BAD_REQUEST, e.to_string()))
+        }
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+/// Wipe the sealed signer's key until it is unsealed again.
+async fn seal(
+    state: State<AppState>,
+    ConnectInfo(caller): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> SealResponse {
+    authorize_seal(&state, &headers)?;
+    let status = state
+        .keys
+        .seal()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    tracing::warn!(%caller, "sealed");
+    Ok(Json(status))
+}
+
+/// Require the sealed signer's token as a bearer token.
+fn authorize_seal(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> std::result::Result<(), (StatusCode, String)> {
+    if state.keys.seal_status().is_none() {
+        return Err(no_sealed_signer());
+    }
+    match bearer_token(headers) {
+        Some(token) if state.keys.authorize_seal(token) => Ok(()),
+        _ => Err((StatusCode::UNAUTHORIZED, "invalid token".to_string())),
+    }
+}
+
+#[derive(Deserialize)]
+struct PauseRequest {
+    key: Option<Address>,
+    method: Option<String>,
+    #[serde(default)]
+    reason: String,
+}
+
+impl PauseRequest {
+    fn scope(&self) -> std::result::Result<PauseScope, (StatusCode, String)> {
+        match (self.key, &self.method) {
+            (None, None) => Ok(PauseScope::All),
+            (Some(key), None) => Ok(PauseScope::Key(key)),
+            (None, Some(method)) => Ok(PauseScope::Method(method.clone())),
+            (Some(_), Some(_)) => Err((
+                StatusCode::BAD_REQUEST,
+                "expected at most one of 'key' or 'method'".to_string(),
+            )),
+        }
+    }
+}
+
+type PauseResponse = std::result::Result<Json<PauseState>, (StatusCode, String)>;
+
+async fn pause_state(state: State<AppState>, headers: HeaderMap) -> PauseResponse {
+    authorize_admin(&state, &headers)?;
+    Ok(Json(state.kill_switch.state()))
+}
+
+/// Stop signing for everything, one key or one method until resumed.
+async fn pause(
+    state: State<AppState>,
+    ConnectInfo(caller): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(request): Json<PauseRequest>,
+) -> PauseResponse {
+    authorize_admin(&state, &headers)?;
+    let scope = request.scope()?;
+    tracing::warn!(%caller, reason = %request.reason, "pause {}", scope);
+    match state.kill_switch.pause(scope, request.reason) {
+        Ok(paused) => Ok(Json(paused)),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+async fn resume(
+    state: State<AppState>,
+    ConnectInfo(caller): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(request): Json<PauseRequest>,
+) -> PauseResponse {
+    authorize_admin(&state, &headers)?;
+    let scope = request.scope()?;
+    tracing::warn!(%caller, "resume {}", scope);
+    match state.kill_switch.resume(&scope) {
+        Ok(paused) => Ok(Json(paused)),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
 
-async fn pub_key(config: State<SignerConfig>) -> Result<String> {
-    let addr = config.address().await?;
-    Ok(addr.to_string())
+/// Require the admin token as a bearer token.
+fn authorize_admin(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> std::result::Result<(), (StatusCode, String)> {
+    let Some(token) = &state.admin_token else {
+        return Err((StatusCode::NOT_FOUND, "admin API is disabled".to_string()));
+    };
+    match bearer_token(headers) {
+        Some(presented) if token.matches(presented) => Ok(()),
+        _ => Err((StatusCode::UNAUTHORIZED, "invalid token".to_string())),
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+fn no_sealed_signer() -> (StatusCode, String) {
+    (
+        StatusCode::NOT_FOUND,
+        "no sealed signer is configured".to_string(),
+    )
+}
+
+#[derive(Deserialize)]
+struct DeriveQuery {
+    index: Option<u32>,
+    path: Option<String>,
+}
+
+/// Address of a child key, without signing anything.
+async fn derive_address(
+    state: State<AppState>,
+    Query(query): Query<DeriveQuery>,
+) -> std::result::Result<String, (StatusCode, String)> {
+    let Some(hd) = &state.hd else {
+        return Err((StatusCode::NOT_FOUND, "derivation is disabled".to_string()));
+    };
+    let child = match (query.index, query.path) {
+        (Some(index), None) => ChildKey::Index(index),
+        (None, Some(path)) => ChildKey::Path(path),
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "expected exactly one of 'index' or 'path'".to_string(),
+            ));
+        }
+    };
+    match hd.derive(&child) {
+        Ok((_, signer)) => Ok(signer.address().to_string()),
+        Err(e) => Err((StatusCode::BAD_REQUEST, e.to_string())),
+    }
 }
 
 fn fix_missing_params(original_bytes: Bytes) -> Bytes {
@@ -54,11 +330,25 @@ fn fix_missing_params(original_bytes: Bytes) -> Bytes {
     Bytes::from(fixed_bytes)
 }
 
-async fn rpc_request(config: State<SignerConfig>, raw_body: Bytes) -> impl IntoResponse {
+async fn rpc_request(
+    state: State<AppState>,
+    ConnectInfo(caller): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    raw_body: Bytes,
+) -> impl IntoResponse {
     let fixed_bytes = fix_missing_params(raw_body);
     let request: JrpcRequest<Params> = serde_json::from_slice(&fixed_bytes).unwrap();
 
-    let response = rpc(config, request).await;
+    let ctx = RequestContext {
+        caller,
+        idempotency_key: headers
+            .get(IDEMPOTENCY_KEY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string),
+    };
+
+    let span = tracing::info_span!("rpc", method = %request.meta.method, id = %request.meta.id);
+    let response = rpc(state, ctx, request).instrument(span).await;
     if response.is_success() {
         (axum::http::StatusCode::OK, Json(response))
     } else {
@@ -70,64 +360,464 @@ async fn rpc_request(config: State<SignerConfig>, raw_body: Bytes) -> impl IntoR
 }
 
 async fn rpc(
-    config: State<SignerConfig>,
-    JrpcRequest { meta, params }: JrpcRequest<Params>,
+    state: State<AppState>,
+    ctx: RequestContext,
+    request: JrpcRequest<Params>,
 ) -> JrpcResponse {
-    let span = tracing::debug_span!("rpc", method = %meta.method, id = %meta.id);
-    let _guard = span.enter();
-
-    match meta.method.as_ref() {
-        SIGN_TX_METHOD => {
-            let Some(raw) = params.first() else {
-                tracing::error!("invalid params");
-                return JrpcResponse::invalid_params(meta.id.clone());
-            };
-
-            let request: TransactionRequest = match serde_json::from_str(raw.get()) {
-                Ok(req) => req,
-                Err(e) => {
-                    tracing::error!("invalid params, deserialize error: {}", e);
-                    return JrpcResponse::invalid_params(meta.id.clone());
-                }
-            };
-
-            JrpcResponse {
-                id: meta.id,
-                payload: match sign(config, request).await {
-                    Ok(result) => ResponsePayload::Success(result),
-                    Err(e) => {
-                        tracing::error!("sign error: {}", e);
-                        ResponsePayload::Failure(e.into())
-                    }
-                },
+    let JrpcRequest { meta, params } = &request;
+    let mut request_metrics = metrics().start_request(method_label(&meta.method));
+
+    let event = AuditEvent {
+        caller: ctx.caller.to_string(),
+        method: meta.method.to_string(),
+        backend: state
+            .keys
+            .default_address()
+            .map_or("unknown", |address| state.keys.backend(&address)),
+        kind: RecordKind::Transaction,
+        summary: Value::Null,
+        decision: Decision::Rejected,
+        reason: None,
+        tx_hash: None,
+        signature: None,
+    };
+
+    let response = match meta.method.as_ref() {
+        SIGN_TX_METHOD => sign_transaction(&state, &ctx, meta.id.clone(), event, params).await,
+        HD_SIGN_TX_METHOD => match &state.hd {
+            Some(hd) => {
+                sign_derived_transaction(&state, hd, &ctx, meta.id.clone(), event, params).await
             }
+            None => JrpcResponse::method_not_found(meta.id.clone()),
+        },
+        SIGN_AUTHORIZATION_METHOD => {
+            sign_authorization(&state, meta.id.clone(), event, params).await
         }
+        ACCOUNTS_METHOD => JrpcResponse {
+            id: meta.id.clone(),
+            payload: match serde_json::value::to_raw_value(&state.keys.addresses()) {
+                Ok(result) => ResponsePayload::Success(result),
+                Err(e) => ResponsePayload::Failure(Error::from(e).into()),
+            },
+        },
         HEALTH_STATUS => JrpcResponse {
             id: meta.id.clone(),
-            payload: ResponsePayload::Success(*Box::new(
-                serde_json::value::RawValue::from_string(
-                    serde_json::to_string(&"ok").expect("failed to serialize"),
-                )
-                .expect("failed to create raw value"),
-            )),
+            payload: match readiness(&state).await.ready {
+                true => string_value("ok"),
+                false => string_value("unavailable"),
+            }
+            .map_or_else(
+                |e| ResponsePayload::Failure(e.into()),
+                ResponsePayload::Success,
+            ),
         },
-        _ => {
-            tracing::error!("invalid method");
-            JrpcResponse::method_not_found(meta.id.clone())
+        _ => match &state.upstream {
+            Some(upstream) => upstream
+                .forward(&request)
+                .await
+                .unwrap_or_else(|e| JrpcResponse {
+                    id: meta.id.clone(),
+                    payload: ResponsePayload::Failure(e.into()),
+                }),
+            None => {
+                tracing::error!("invalid method");
+                JrpcResponse::method_not_found(meta.id.clone())
+            }
+        },
+    };
+
+    request_metrics.success(response.is_success());
+    response
+}
+
+/// Method name used as a metric label, bounded to the methods we serve.
+fn method_label(method: &str) -> &'static str {
+    match method {
+        SIGN_TX_METHOD => SIGN_TX_METHOD,
+        ACCOUNTS_METHOD => ACCOUNTS_METHOD,
+        HD_SIGN_TX_METHOD => HD_SIGN_TX_METHOD,
+        SIGN_AUTHORIZATION_METHOD => SIGN_AUTHORIZATION_METHOD,
+        HEALTH_STATUS => HEALTH_STATUS,
+        _ => "unknown",
+    }
+}
+
+async fn sign_transaction(
+    state: &AppState,
+    ctx: &RequestContext,
+    id: Id,
+    event: AuditEvent,
+    params: &Params,
+) -> JrpcResponse {
+    let mut request = match parse_transaction(&event.method, params) {
+        Ok(request) => request,
+        Err(reason) => return reject(state, id, event, "invalid_params", reason),
+    };
+
+    let from = match state.keys.resolve(request.from) {
+        Ok(from) => from,
+        Err(Error::Sealed) => return refuse(state, id, event, "sealed", Error::Sealed),
+        Err(e) => return reject(state, id, event, "unknown_key", e.to_string()),
+    };
+    request.from = Some(from);
+
+    let event = AuditEvent {
+        backend: state.keys.backend(&from),
+        summary: audit::tx_summary(&request),
+        ..event
+    };
+
+    sign_checked(state, ctx, id, event, request, &state.keys.wallet()).await
+}
+
+/// Sign with a child key of the derivation subtree. The second parameter is
+/// the child index below the subtree or a full derivation path inside it.
+async fn sign_derived_transaction(
+    state: &AppState,
+    hd: &HdKeys,
+    ctx: &RequestContext,
+    id: Id,
+    event: AuditEvent,
+    params: &Params,
+) -> JrpcResponse {
+    let mut request = match parse_transaction(&event.method, params) {
+        Ok(request) => request,
+        Err(reason) => return reject(state, id, event, "invalid_params", reason),
+    };
+
+    let child: ChildKey = match params.get(1).map(|raw| serde_json::from_str(raw.get())) {
+        Some(Ok(child)) => child,
+        Some(Err(e)) => {
+            let reason = format!(
+                "invalid derivation index or path: {}",
+                deserialize_error(&e)
+            );
+            return reject(state, id, event, "invalid_params", reason);
+        }
+        None => {
+            let reason = "missing derivation index or path".to_string();
+            return reject(state, id, event, "invalid_params", reason);
         }
+    };
+    let (path, signer) = match hd.derive(&child) {
+        Ok(derived) => derived,
+        Err(e) => return reject(state, id, event, "derivation", e.to_string()),
+    };
+
+    let from = signer.address();
+    if request.from.is_some_and(|requested| requested != from) {
+        let reason = format!("from does not match the key at {path}");
+        return reject(state, id, event, "unknown_key", reason);
+    }
+    request.from = Some(from);
+
+    let mut summary = audit::tx_summary(&request);
+    summary["derivation_path"] = Value::String(path);
+    let event = AuditEvent {
+        backend: HD_BACKEND,
+        summary,
+        ..event
+    };
+
+    sign_checked(state, ctx, id, event, request, &EthereumWallet::new(signer)).await
+}
+
+/// EIP-7702 authorization to sign, with the key that signs it.
+#[derive(Deserialize)]
+struct AuthorizationRequest {
+    from: Option<Address>,
+    #[serde(flatten)]
+    authorization: Authorization,
+}
+
+/// Sign an EIP-7702 authorization delegating the key's account to a
+/// contract the policy allows.
+async fn sign_authorization(
+    state: &AppState,
+    id: Id,
+    event: AuditEvent,
+    params: &Params,
+) -> JrpcResponse {
+    let request: AuthorizationRequest =
+        match params.first().map(|raw| serde_json::from_str(raw.get())) {
+            Some(Ok(request)) => request,
+            Some(Err(e)) => {
+                let reason = format!("invalid authorization: {}", deserialize_error(&e));
+                return reject(state, id, event, "invalid_params", reason);
+            }
+            None => {
+                let reason = "missing params".to_string();
+                return reject(state, id, event, "invalid_params", reason);
+            }
+        };
+    let authorization = request.authorization;
+
+    let from = match state.keys.resolve(request.from) {
+        Ok(from) => from,
+        Err(Error::Sealed) => return refuse(state, id, event, "sealed", Error::Sealed),
+        Err(e) => return reject(state, id, event, "unknown_key", e.to_string()),
+    };
+    let backend = state.keys.backend(&from);
+    let event = AuditEvent {
+        backend,
+        kind: RecordKind::Authorization,
+        summary: audit::authorization_summary(from, &authorization),
+        ..event
+    };
+
+    if let Err(e) = state.kill_switch.check(&event.method, Some(from)) {
+        tracing::warn!("refused: {}", e);
+        return refuse(state, id, event, "paused", e);
+    }
+    if let Err(e) = state.policy.check_authorization(&authorization) {
+        tracing::warn!("rejected by policy: {}", e);
+        return reject(state, id, event, "policy", e.to_string());
+    }
+
+    let started = Instant::now();
+    let signed = state
+        .keys
+        .sign_authorization(from, authorization.clone())
+        .await;
+    metrics().record_backend(backend, started, signed.is_ok());
+    let signed = signed.and_then(|signed| {
+        verify::check_authorization(from, &authorization, &signed).inspect_err(|e| {
+            metrics().record_self_check_failure(backend);
+            tracing::error!(backend, "withheld signature: {}", e);
+        })?;
+        Ok(signed)
+    });
+    let event = match &signed {
+        Ok(signed) => AuditEvent {
+            decision: Decision::Signed,
+            signature: signed.signature().ok(),
+            ..event
+        },
+        Err(e) => AuditEvent {
+            decision: Decision::Failed,
+            reason: Some(e.to_string()),
+            ..event
+        },
+    };
+
+    // The signature is only released once its audit record is written.
+    let result = state
+        .record(event)
+        .and(signed)
+        .and_then(|signed| Ok(serde_json::value::to_raw_value(&signed)?));
+    JrpcResponse {
+        id,
+        payload: match result {
+            Ok(result) => ResponsePayload::Success(result),
+            Err(e) => ResponsePayload::Failure(e.into()),
+        },
+    }
+}
+
+/// Backend label of keys derived per request.
+const HD_BACKEND: &str = "hd";
+
+fn parse_transaction(
+    method: &str,
+    params: &Params,
+) -> std::result::Result<TransactionRequest, String> {
+    let Some(raw) = params.first() else {
+        tracing::error!(method, "invalid params");
+        return Err("missing params".to_string());
+    };
+    let mut request: TransactionRequest = serde_json::from_str(raw.get()).map_err(|e| {
+        let reason = deserialize_error(&e);
+        tracing::error!(method, "invalid params, deserialize error: {}", reason);
+        format!("invalid params: {reason}")
+    })?;
+    blob::check_sidecar(&mut request).map_err(|e| {
+        tracing::error!(method, "invalid params: {}", e);
+        e.to_string()
+    })?;
+    Ok(request)
+}
+
+/// Describe why params failed to deserialize without the offending value,
+/// which serde quotes and may be calldata or a key the caller sent.
+fn deserialize_error(e: &serde_json::Error) -> String {
+    let kind = match e.classify() {
+        serde_json::error::Category::Io => "io",
+        serde_json::error::Category::Syntax => "syntax",
+        serde_json::error::Category::Data => "data",
+        serde_json::error::Category::Eof => "eof",
+    };
+    format!("{kind} error at line {} column {}", e.line(), e.column())
+}
+
+/// Apply the kill switch and the signing policy, then sign with `wallet`,
+/// replaying retries.
+async fn sign_checked(
+    state: &AppState,
+    ctx: &RequestContext,
+    id: Id,
+    event: AuditEvent,
+    request: TransactionRequest,
+    wallet: &EthereumWallet,
+) -> JrpcResponse {
+    if let Err(e) = state.kill_switch.check(&event.method, request.from) {
+        tracing::warn!("refused: {}", e);
+        return refuse(state, id, event, "paused", e);
+    }
+
+    if let Err(e) = state.policy.check(&request) {
+        tracing::warn!("rejected by policy: {}", e);
+        return reject(state, id, event, "policy", e.to_string());
+    }
+
+    let result = match &state.idempotency {
+        Some(cache) => match idempotent_sign(state, cache, ctx, event, request, wallet).await {
+            Ok(raw) => string_value(&raw),
+            Err(e) => Err(e),
+        },
+        None => match sign_and_audit(state, event, request, wallet).await {
+            Ok(envelope) => encode_raw(&envelope),
+            Err(e) => Err(e),
+        },
+    };
+
+    JrpcResponse {
+        id,
+        payload: match result {
+            Ok(result) => ResponsePayload::Success(result),
+            Err(e) => ResponsePayload::Failure(e.into()),
+        },
+    }
+}
+
+/// Answer a retried request with the response produced the first time. The key
+/// is the caller's `Idempotency-Key` header, or else the canonical request hash.
+async fn idempotent_sign(
+    state: &AppState,
+    cache: &IdempotencyCache,
+    ctx: &RequestContext,
+    event: AuditEvent,
+    request: TransactionRequest,
+    wallet: &EthereumWallet,
+) -> Result<String> {
+    let request_hash = keccak256(serde_json::to_vec(&request)?);
+    let key = ctx
+        .idempotency_key
+        .clone()
+        .unwrap_or_else(|| request_hash.to_string());
+
+    let pending = match cache.reserve(&key, request_hash).await {
+        Ok(Reservation::Pending(pending)) => pending,
+        Ok(Reservation::Cached(raw)) => {
+            let envelope = TxEnvelope::decode_2718(&mut alloy::hex::decode(&raw)?.as_slice())?;
+            info!(tx_hash = %envelope.tx_hash(), "replay signed tx");
+            state.record(AuditEvent {
+                decision: Decision::Replayed,
+                tx_hash: Some(*envelope.tx_hash()),
+                signature: Some(*envelope.signature()),
+                ..event
+            })?;
+            // A matching retry carries the same sidecar.
+            return Ok(raw_hex(&blob::attach_sidecar(envelope, request.sidecar)));
+        }
+        Err(e) => {
+            metrics().record_rejection(&event.method, "idempotency_conflict");
+            state.record(AuditEvent {
+                reason: Some(e.to_string()),
+                ..event
+            })?;
+            return Err(e);
+        }
+    };
+
+    let envelope = sign_and_audit(state, event, request, wallet).await?;
+    // Blob sidecars are up to several MB, so only the signed transaction is
+    // kept.
+    pending.complete(raw_hex(&blob::strip_sidecar(envelope.clone())))?;
+    Ok(raw_hex(&envelope))
+}
+
+async fn sign_and_audit(
+    state: &AppState,
+    event: AuditEvent,
+    request: TransactionRequest,
+    wallet: &EthereumWallet,
+) -> Result<TxEnvelope> {
+    let signed = sign(state, event.backend, request, wallet).await;
+    let event = match &signed {
+        Ok(envelope) => AuditEvent {
+            decision: Decision::Signed,
+            tx_hash: Some(*envelope.tx_hash()),
+            signature: Some(*envelope.signature()),
+            ..event
+        },
+        Err(e) => {
+            tracing::error!("sign error: {}", e);
+            AuditEvent {
+                decision: Decision::Failed,
+                reason: Some(e.to_string()),
+                ..event
+            }
+        }
+    };
+
+    // The signature is only released once its audit record is written.
+    state.record(event)?;
+    signed
+}
+
+/// Record a rejected request and answer it with `invalid_params`.
+fn reject(
+    state: &AppState,
+    id: Id,
+    event: AuditEvent,
+    kind: &'static str,
+    reason: String,
+) -> JrpcResponse {
+    metrics().record_rejection(&event.method, kind);
+    match state.record(AuditEvent {
+        reason: Some(reason),
+        ..event
+    }) {
+        Ok(()) => JrpcResponse::invalid_params(id),
+        Err(e) => JrpcResponse::internal_error_message(id, e.to_string().into()),
+    }
+}
+
+/// Record a refused request and answer it with `error`.
+fn refuse(
+    state: &AppState,
+    id: Id,
+    event: AuditEvent,
+    kind: &'static str,
+    error: Error,
+) -> JrpcResponse {
+    metrics().record_rejection(&event.method, kind);
+    let recorded = state.record(AuditEvent {
+        reason: Some(error.to_string()),
+        ..event
+    });
+    JrpcResponse {
+        id,
+        payload: ResponsePayload::Failure(match recorded {
+            Ok(()) => error.into(),
+            Err(e) => e.into(),
+        }),
     }
 }
 
 async fn sign(
-    config: State<SignerConfig>,
+    state: &AppState,
+    backend: &'static str,
     request: TransactionRequest,
-) -> Result<Box<serde_json::value::RawValue>> {
+    wallet: &EthereumWallet,
+) -> Result<TxEnvelope> {
+    let expected = request.clone();
     let TransactionRequest {
         from, to, input, ..
     } = request.clone();
     let TransactionInput { input, data } = input;
-    let input = input.map(|i| i.to_string());
-    let data = data.map(|d| d.to_string());
+    let input = input.map(|i| state.redaction.apply(&i));
+    let data = data.map(|d| state.redaction.apply(&d));
 
     let to = to.map(|kind| match kind {
         TxKind::Create => String::from("create"),
@@ -140,37 +830,72 @@ async fn sign(
         input = %input.unwrap_or_default(),
         data = %data.unwrap_or_default()
     );
-    let _guard = span.enter();
 
-    let mut req_hash = DefaultHasher::new();
-    request.clone().hash(&mut req_hash);
+    // Instrumenting rather than entering keeps the span current across awaits,
+    // so backend calls inherit the caller's trace context.
+    async move {
+        let started = Instant::now();
+        let tx_envelop = request.build(wallet).await.map_err(Error::from);
+        metrics().record_backend(backend, started, tx_envelop.is_ok());
+        let tx_envelop = tx_envelop?;
 
-    let wallet = config.wallet().await?;
+        if let Err(e) = verify::check_signed(&expected, &tx_envelop) {
+            metrics().record_self_check_failure(backend);
+            tracing::error!(backend, "withheld signature: {}", e);
+            return Err(e);
+        }
 
-    let tx_envelop = request.build(&wallet).await?;
+        info!(
+            tx_hash = %tx_envelop.tx_hash(),
+            hex = %state.redaction.apply(&tx_envelop.encoded_2718()),
+            "sign tx"
+        );
 
-    let mut tx_hash = DefaultHasher::new();
-    tx_envelop.tx_hash().hash(&mut tx_hash);
+        Ok(tx_envelop)
+    }
+    .instrument(span)
+    .await
+}
 
+fn raw_hex(tx_envelop: &TxEnvelope) -> String {
     let mut encoded_tx = Vec::<u8>::new();
 
     tx_envelop.encode_2718(&mut encoded_tx);
 
     let hex_string: String = encoded_tx.iter().map(|b| format!("{:02x?}", b)).collect();
 
-    let raw_string = format!("0x{}", hex_string);
+    format!("0x{}", hex_string)
+}
 
-    info!(hex = %raw_string, "sign tx");
+fn encode_raw(tx_envelop: &TxEnvelope) -> Result<Box<serde_json::value::RawValue>> {
+    string_value(&raw_hex(tx_envelop))
+}
 
+fn string_value(s: &str) -> Result<Box<serde_json::value::RawValue>> {
     Ok(serde_json::value::RawValue::from_string(
-        serde_json::to_string(&raw_string)?,
+        serde_json::to_string(s)?,
     )?)
 }
 
-pub fn routes(state: SignerConfig) -> Router {
+/// Largest JSON-RPC request body. Blobs are 128 KiB each and hex encoded in
+/// JSON, so a full sidecar does not fit axum's default of 2 MiB.
+const RPC_BODY_LIMIT: usize = 4 << 20;
+
+pub fn routes(state: AppState) -> Router {
     Router::new()
+        .route("/livez", get(|| async { "OK" }))
         .route("/healthz", get(|| async { "OK" }))
+        .route("/readyz", get(ready))
         .route("/pub", get(pub_key))
-        .route("/", post(rpc_request))
+        .route("/derive", get(derive_address))
+        .route("/seal-status", get(seal_status))
+        .route("/unseal", post(unseal))
+        .route("/seal", post(seal))
+        .route("/admin/pause", get(pause_state).post(pause))
+        .route("/admin/resume", post(resume))
+        .route(
+            "/",
+            post(rpc_request).layer(DefaultBodyLimit::max(RPC_BODY_LIMIT)),
+        )
         .with_state(state)
 }
@@ -0,0 +1,230 @@
+use std::{borrow::Cow, fmt, path::Path, sync::Arc};
+
+use schemars::{JsonSchema, Schema, SchemaGenerator};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::Zeroizing;
+
+use crate::prelude::*;
+
+/// Prefix of a secret value that names the file holding the secret, e.g.
+/// `file:/run/secrets/private_key`.
+pub const FILE_PREFIX: &str = "file:";
+
+/// Secret string such as a private key or password. Clones share one
+/// buffer, which is zeroed when the last handle is dropped. It prints as
+/// `[hidden]` and fails to serialize, so it cannot leak through logs or a
+/// written-back config.
+#[derive(Clone)]
+pub struct Secret(Arc<SecretBuf>);
+
+struct SecretBuf {
+    value: Zeroizing<String>,
+    #[cfg(unix)]
+    locked: std::sync::atomic::AtomicBool,
+}
+
+impl Secret {
+    pub fn new(value: String) -> Self {
+        Secret(Arc::new(SecretBuf {
+            value: Zeroizing::new(value),
+            #[cfg(unix)]
+            locked: std::sync::atomic::AtomicBool::new(false),
+        }))
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0.value
+    }
+
+    /// Whether `candidate` equals the secret, such as a presented token.
+    pub fn matches(&self, candidate: &str) -> bool {
+        use alloy::primitives::keccak256;
+        // Comparing digests keeps the time taken independent of the secret.
+        keccak256(candidate) == keccak256(self.expose())
+    }
+
+    /// Keep the pages holding the secret out of swap.
+    #[cfg(unix)]
+    pub fn lock(&self) -> Result<()> {
+        use std::sync::atomic::Ordering;
+
+        let value = self.expose();
+        if value.is_empty() || self.0.locked.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        // SAFETY: the range is the live heap buffer of `value`, which is
+        // never reallocated while the secret exists.
+        if unsafe { libc::mlock(value.as_ptr().cast(), value.len()) } != 0 {
+            self.0.locked.store(false, Ordering::SeqCst);
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn lock(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Resolve a secret value, reading it from a file when it carries the
+    /// [`FILE_PREFIX`]. Literal values are returned unchanged.
+    pub fn resolve(&self, strict_permissions: bool) -> Result<Secret> {
+        match self.expose().strip_prefix(FILE_PREFIX) {
+            Some(path) => read_file(path, strict_permissions),
+            None => Ok(self.clone()),
+        }
+    }
+}
+
+impl Drop for SecretBuf {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if self.locked.load(std::sync::atomic::Ordering::SeqCst) {
+            // SAFETY: unlocks the range locked in `Secret::lock`; the buffer
+            // is zeroed afterwards when `value` drops.
+            unsafe { libc::munlock(self.value.as_ptr().cast(), self.value.len()) };
+        }
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Secret::new(value)
+    }
+}
+
+impl std::str::FromStr for Secret {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(Secret::new(s.to_string()))
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[hidden]")
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, _serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        Err(serde::ser::Error::custom(
+            "secret values are not serializable",
+        ))
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Secret::new)
+    }
+}
+
+impl JsonSchema for Secret {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        String::schema_name()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        String::json_schema(generator)
+    }
+}
+
+/// Read a secret file, dropping the trailing newline most editors and
+/// `echo` add. Files other users can read are refused when
+/// `strict_permissions` is set and logged otherwise, since Docker and
+/// Kubernetes mount secrets world-readable by default.
+pub fn read_file(path: impl AsRef<Path>, strict_permissions: bool) -> Result<Secret> {
+    let path = path.as_ref();
+    check_permissions(path, strict_permissions)?;
+    read_shared_file(path)
+}
+
+/// Read a secret file that other users may read by design, such as a
+/// Kubernetes service account token, without the permission check.
+pub fn read_shared_file(path: impl AsRef<Path>) -> Result<Secret> {
+    let content = Zeroizing::new(std::fs::read_to_string(path)?);
+    Ok(Secret::new(
+        content.trim_end_matches(['\r', '\n']).to_string(),
+    ))
+}
+
+/// Stop the kernel from writing core dumps, which would contain every
+/// secret in memory.
+#[cfg(unix)]
+pub fn disable_core_dumps() -> Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: plain system calls with a valid argument.
+    if unsafe { libc::setrlimit(libc::RLIMIT_CORE, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    #[cfg(target_os = "linux")]
+    if unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn disable_core_dumps() -> Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn check_permissions(path: &Path, strict: bool) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = std::fs::metadata(path)?.permissions().mode();
+    if mode & 0o077 == 0 {
+        return Ok(());
+    }
+    if strict {
+        return Err(Error::InsecureSecretFile(path.display().to_string()));
+    }
+    tracing::warn!(
+        "secret file {} is accessible by other users (mode {:o}), restrict it to 0600 or 0400",
+        path.display(),
+        mode & 0o777
+    );
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_path: &Path, _strict: bool) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn read_secret_file() {
+        let path = std::env::temp_dir().join(format!("eth-signer-secret-{}", std::process::id()));
+        std::fs::write(&path, "0xabc\n").unwrap();
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        let value = Secret::new(format!("{FILE_PREFIX}{}", path.display()));
+        assert_eq!(value.resolve(true).unwrap().expose(), "0xabc");
+        let literal = Secret::new("0xdef".to_string());
+        assert_eq!(literal.resolve(true).unwrap().expose(), "0xdef");
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(value.resolve(true).is_err());
+        assert_eq!(value.resolve(false).unwrap().expose(), "0xabc");
+
+        assert_eq!(format!("{value:?}"), "[hidden]");
+        assert!(serde_json::to_string(&value).is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
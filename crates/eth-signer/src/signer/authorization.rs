@@ -0,0 +1,66 @@
+use alloy::{
+    eips::eip7702::{Authorization, SignedAuthorization},
+    primitives::U256,
+};
+
+use super::KeySigner;
+use crate::prelude::*;
+
+/// Sign `authorization` with `signer`, over
+/// `keccak256(0x05 || rlp(authorization))`. A key bound to one chain refuses
+/// authorizations for other chains, and for chain 0, which authorizes every
+/// chain.
+pub(super) async fn sign(
+    signer: &dyn KeySigner,
+    authorization: Authorization,
+) -> Result<SignedAuthorization> {
+    if let Some(chain_id) = signer.bound_chain_id()
+        && authorization.chain_id != U256::from(chain_id)
+    {
+        return Err(Error::PolicyViolation(format!(
+            "authorization for chain {} from a key bound to chain {chain_id}",
+            authorization.chain_id
+        )));
+    }
+    let signature = signer.sign_digest(&authorization.signature_hash()).await?;
+    Ok(authorization.into_signed(signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::{
+        primitives::Address,
+        signers::{Signer, local::PrivateKeySigner},
+    };
+
+    #[tokio::test]
+    async fn sign_authorizations() {
+        let signer = PrivateKeySigner::random();
+        let authorization = Authorization {
+            chain_id: U256::from(1),
+            address: Address::repeat_byte(7),
+            nonce: 3,
+        };
+        let signed = sign(&signer, authorization.clone()).await.unwrap();
+        assert_eq!(signed.inner(), &authorization);
+        assert_eq!(signed.recover_authority().unwrap(), signer.address());
+        assert_eq!(
+            signed.signature().unwrap(),
+            signer
+                .sign_hash(&authorization.signature_hash())
+                .await
+                .unwrap()
+        );
+
+        // A key bound to chain 1 signs neither another chain nor all chains.
+        let bound = signer.with_chain_id(Some(1));
+        for chain_id in [0, 5] {
+            let authorization = Authorization {
+                chain_id: U256::from(chain_id),
+                ..authorization.clone()
+            };
+            assert!(sign(&bound, authorization).await.is_err());
+        }
+    }
+}
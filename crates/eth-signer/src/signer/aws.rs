@@ -0,0 +1,154 @@
+use alloy::signers::aws::{
+    aws_config::{self, BehaviorVersion, Region, retry::RetryConfig, sts::AssumeRoleProvider},
+    aws_sdk_kms::{
+        self,
+        config::{
+            ConfigBag, Credentials, Intercept, RuntimeComponents,
+            interceptors::BeforeTransmitInterceptorContextMut,
+        },
+    },
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+use crate::secret::Secret;
+
+/// Static credentials, used instead of the default AWS credential chain.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    /// Secret access key, or `file:<path>`.
+    pub secret_access_key: Secret,
+    /// Session token of temporary credentials, or `file:<path>`.
+    #[serde(default)]
+    pub session_token: Option<Secret>,
+}
+
+impl AwsCredentials {
+    pub fn secrets_mut(&mut self) -> Vec<&mut Secret> {
+        std::iter::once(&mut self.secret_access_key)
+            .chain(self.session_token.as_mut())
+            .collect()
+    }
+}
+
+/// Role assumed through STS before calling KMS, e.g. for a key owned by
+/// another account.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AwsAssumeRole {
+    pub role_arn: String,
+    #[serde(default)]
+    pub external_id: Option<String>,
+    /// Session name shown in CloudTrail [default: eth-signer].
+    #[serde(default)]
+    pub session_name: Option<String>,
+}
+
+/// Connection settings of a KMS client. Unset values come from the default
+/// AWS configuration chain: environment, shared config files, instance
+/// metadata.
+pub struct KmsClientOptions<'a> {
+    pub region: Option<&'a str>,
+    /// Endpoint override for every AWS call, e.g. LocalStack.
+    pub endpoint: Option<&'a str>,
+    pub profile: Option<&'a str>,
+    pub credentials: Option<&'a AwsCredentials>,
+    pub assume_role: Option<&'a AwsAssumeRole>,
+}
+
+pub async fn kms_client(options: KmsClientOptions<'_>) -> aws_sdk_kms::Client {
+    // Retries and timeouts are applied around each call by `Retry`.
+    let mut loader =
+        aws_config::defaults(BehaviorVersion::latest()).retry_config(RetryConfig::disabled());
+    if let Some(region) = options.region {
+        loader = loader.region(Region::new(region.to_string()));
+    }
+    if let Some(endpoint) = options.endpoint {
+        loader = loader.endpoint_url(endpoint);
+    }
+    if let Some(profile) = options.profile {
+        loader = loader.profile_name(profile);
+    }
+    if let Some(credentials) = options.credentials {
+        loader = loader.credentials_provider(Credentials::new(
+            &credentials.access_key_id,
+            credentials.secret_access_key.expose(),
+            credentials
+                .session_token
+                .as_ref()
+                .map(|token| token.expose().to_string()),
+            None,
+            "eth-signer",
+        ));
+    }
+    let config = loader.load().await;
+
+    let mut kms = aws_sdk_kms::config::Builder::from(&config).interceptor(TraceContextInterceptor);
+    if let Some(role) = options.assume_role {
+        let mut provider = AssumeRoleProvider::builder(&role.role_arn)
+            .session_name(role.session_name.as_deref().unwrap_or("eth-signer"))
+            .configure(&config);
+        if let Some(external_id) = &role.external_id {
+            provider = provider.external_id(external_id);
+        }
+        kms = kms.credentials_provider(provider.build().await);
+    }
+    aws_sdk_kms::Client::from_conf(kms.build())
+}
+
+/// KMS key ID from a key ID or ARN, or from an alias name with or without
+/// its `alias/` prefix.
+pub fn key_id(key: Option<&str>, alias: Option<&str>) -> Result<String> {
+    match (key, alias) {
+        (Some(key), None) => Ok(key.to_string()),
+        (None, Some(alias)) if alias.starts_with("alias/") || alias.starts_with("arn:") => {
+            Ok(alias.to_string())
+        }
+        (None, Some(alias)) => Ok(format!("alias/{alias}")),
+        _ => Err(Error::InvalidConfig(
+            "aws_kms signer needs exactly one of key and alias".to_string(),
+        )),
+    }
+}
+
+/// Adds the current span's W3C trace context to outgoing KMS requests.
+#[derive(Debug)]
+struct TraceContextInterceptor;
+
+impl Intercept for TraceContextInterceptor {
+    fn name(&self) -> &'static str {
+        "TraceContextInterceptor"
+    }
+
+    fn modify_before_transmit(
+        &self,
+        context: &mut BeforeTransmitInterceptorContextMut<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> std::result::Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let headers = context.request_mut().headers_mut();
+        for (name, value) in crate::otel::current_context_headers() {
+            headers.insert(name, value);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_key_id() {
+        assert_eq!(key_id(Some("1234abcd"), None).unwrap(), "1234abcd");
+        assert_eq!(key_id(None, Some("hot")).unwrap(), "alias/hot");
+        assert_eq!(key_id(None, Some("alias/hot")).unwrap(), "alias/hot");
+        let arn = "arn:aws:kms:us-east-1:111122223333:alias/hot";
+        assert_eq!(key_id(None, Some(arn)).unwrap(), arn);
+        assert!(key_id(Some("1234abcd"), Some("hot")).is_err());
+        assert!(key_id(None, None).is_err());
+    }
+}
@@ -1,71 +1,346 @@
+use alloy::primitives::Address;
+use clap::ValueEnum;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::fmt;
 
-#[derive(Serialize, Deserialize, Clone)]
+use super::{AwsAssumeRole, AwsCredentials, RetryConfig, VaultConfig};
+use crate::secret::Secret;
+
+/// Ethereum derivation path prefix; the account index is appended.
+pub const DEFAULT_DERIVATION_PATH: &str = "m/44'/60'/0'/0";
+
+/// Signing backend configuration. Secret fields are [`Secret`]s, so the
+/// derived `Debug` hides them and serializing fails.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 #[serde(tag = "type")]
-#[serde(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub enum SignerConfig {
-    PrivateKey(String),
-    Mnemonic(String),
+    PrivateKey {
+        /// Hex private key, or `file:<path>`.
+        key: Secret,
+    },
+    /// BIP-39 mnemonic. Accounts `index` through `index + count - 1` are
+    /// derived at `<derivation_path>/<index>`.
+    Mnemonic {
+        /// Mnemonic phrase, or `file:<path>`.
+        phrase: Secret,
+        /// BIP-39 passphrase, or `file:<path>`.
+        #[serde(default)]
+        passphrase: Option<Secret>,
+        #[serde(default)]
+        language: MnemonicLanguage,
+        #[serde(default = "default_derivation_path")]
+        derivation_path: String,
+        #[serde(default)]
+        index: u32,
+        /// Number of consecutive accounts to derive.
+        #[serde(default = "default_count")]
+        count: u32,
+    },
+    #[serde(alias = "keystore")]
     KeyStore {
         path: String,
-        password: String,
+        /// Keystore password, or `file:<path>`.
+        password: Secret,
+    },
+    /// Directory of V3 keystore files, rescanned for new files.
+    #[serde(alias = "keystore_dir")]
+    KeyStoreDir {
+        path: String,
+        /// Password shared by keystores without their own password file, or
+        /// `file:<path>`.
+        #[serde(default)]
+        password: Option<Secret>,
+        /// Directory of per-account password files named after the address.
+        #[serde(default)]
+        password_dir: Option<String>,
+        /// Seconds between scans for new keystores, 0 disables.
+        #[serde(default = "default_watch_interval")]
+        watch_interval_secs: u64,
     },
     AzureKeyVault {
         key: String,
-        secret: String,
+        secret: Secret,
     },
+    /// AWS KMS key. The connection settings default to the AWS
+    /// configuration chain.
+    #[serde(alias = "awskms")]
     AwsKms {
-        key: String,
+        /// Key ID or ARN, or an alias as `alias/<name>` or alias ARN.
+        #[serde(default)]
+        key: Option<String>,
+        /// Alias name, with or without the `alias/` prefix; instead of `key`.
+        #[serde(default)]
+        alias: Option<String>,
+        #[serde(default)]
+        region: Option<String>,
+        /// Endpoint override for every AWS call, e.g. `http://localhost:4566`
+        /// for LocalStack.
+        #[serde(default)]
+        endpoint: Option<String>,
+        /// Profile of the shared AWS config and credentials files.
+        #[serde(default)]
+        profile: Option<String>,
+        #[serde(default)]
+        credentials: Option<AwsCredentials>,
+        #[serde(default)]
+        assume_role: Option<AwsAssumeRole>,
+        /// Chain the key signs for. EIP-155 is applied and transactions for
+        /// other chains are refused; 0 signs for any chain.
+        #[serde(default = "default_aws_chain_id")]
+        chain_id: u64,
+        #[serde(default)]
+        retry: RetryConfig,
     },
+    #[serde(alias = "gcpkms")]
     GoogleKms {
         project_id: String,
         location: String,
         key_ring: String,
         key: String,
-        version: u64,
+        /// Key version. When unset, the newest enabled version is used and
+        /// resolved again every `refresh_interval_secs`.
+        #[serde(default)]
+        version: Option<u64>,
+        /// API endpoint, e.g. an emulator or a Private Service Connect
+        /// address [default: https://cloudkms.googleapis.com].
+        #[serde(default)]
+        endpoint: Option<String>,
+        /// Service account key file, instead of the default credentials.
+        #[serde(default)]
+        credentials_file: Option<String>,
+        /// Seconds between version checks without a fixed `version`, 0
+        /// disables.
+        #[serde(default = "default_key_version_refresh")]
+        refresh_interval_secs: u64,
+        #[serde(default)]
+        retry: RetryConfig,
+    },
+    /// One key served by several KMS replicas, such as an AWS multi-region
+    /// key or a key imported into several Google Cloud locations. Replicas
+    /// are asked in order, moving on when one fails or, with
+    /// `hedge_after_ms`, is slow.
+    Replicas {
+        /// `aws_kms` or `google_kms` signers with a fixed version, all
+        /// holding the same key.
+        replicas: Vec<SignerConfig>,
+        /// Milliseconds to wait for a replica before also asking the next
+        /// one. The first signature wins. When unset, the next replica is
+        /// only asked after an error.
+        #[serde(default)]
+        hedge_after_ms: Option<u64>,
     },
     AlicloudKms {
         key: String,
-        secret: String,
+        secret: Secret,
+    },
+    /// Private key read from a Vault or OpenBao KV version 2 secret, and read
+    /// again when the secret gets a new version.
+    VaultKv {
+        vault: VaultConfig,
+        #[serde(default = "default_kv_mount")]
+        mount: String,
+        path: String,
+        /// Field of the secret holding the hex private key.
+        #[serde(default = "default_kv_field")]
+        field: String,
+        /// Seconds between version checks, 0 disables.
+        #[serde(default = "default_refresh_interval")]
+        refresh_interval_secs: u64,
+    },
+    /// Account of the `vault-ethereum` plugin; the key never leaves Vault.
+    VaultPlugin {
+        vault: VaultConfig,
+        /// Mount path of the plugin, e.g. `ethereum`.
+        mount: String,
+        /// Account name in the plugin.
+        key: String,
     },
+    /// secp256k1 key in a PKCS#11 token such as an HSM. The token is chosen
+    /// by exactly one of `slot` and `token_label`.
+    Pkcs11 {
+        /// Path of the PKCS#11 module, e.g. `/usr/lib/softhsm/libsofthsm2.so`.
+        module: String,
+        #[serde(default)]
+        slot: Option<u64>,
+        #[serde(default)]
+        token_label: Option<String>,
+        /// User PIN, or `file:<path>`.
+        pin: Secret,
+        /// `CKA_LABEL` shared by the private and public key objects.
+        key_label: String,
+        /// Sessions open at once for concurrent requests.
+        #[serde(default = "default_sessions")]
+        sessions: usize,
+    },
+    /// Private key or mnemonic split into Shamir shares held by operators.
+    /// The service starts without it and loads it once `threshold` shares
+    /// are submitted to `/unseal`.
+    Sealed {
+        /// Shares needed to reconstruct the secret.
+        threshold: u8,
+        /// Address of the reconstructed key, or of the first mnemonic
+        /// account. Shares that reconstruct anything else are discarded.
+        #[schemars(with = "String")]
+        address: Address,
+        /// Bearer token of the unseal and seal endpoints, or `file:<path>`.
+        token: Secret,
+        #[serde(default)]
+        secret: SealedSecret,
+    },
+}
+
+/// Kind of secret a sealed signer reconstructs.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(tag = "type")]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum SealedSecret {
+    /// Hex private key.
+    #[default]
+    PrivateKey,
+    /// BIP-39 mnemonic phrase, derived as by the `mnemonic` signer.
+    Mnemonic {
+        /// BIP-39 passphrase, or `file:<path>`.
+        #[serde(default)]
+        passphrase: Option<Secret>,
+        #[serde(default)]
+        language: MnemonicLanguage,
+        #[serde(default = "default_derivation_path")]
+        derivation_path: String,
+        #[serde(default)]
+        index: u32,
+        /// Number of consecutive accounts to derive.
+        #[serde(default = "default_count")]
+        count: u32,
+    },
+}
+
+/// BIP-39 wordlist of a mnemonic phrase.
+#[derive(ValueEnum, Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MnemonicLanguage {
+    #[default]
+    English,
+    ChineseSimplified,
+    ChineseTraditional,
+    Czech,
+    French,
+    Italian,
+    Japanese,
+    Korean,
+    Portuguese,
+    Spanish,
+}
+
+fn default_derivation_path() -> String {
+    DEFAULT_DERIVATION_PATH.to_string()
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+fn default_watch_interval() -> u64 {
+    10
+}
+
+fn default_kv_mount() -> String {
+    "secret".to_string()
+}
+
+fn default_kv_field() -> String {
+    "private_key".to_string()
+}
+
+fn default_refresh_interval() -> u64 {
+    60
+}
+
+fn default_sessions() -> usize {
+    4
+}
+
+fn default_aws_chain_id() -> u64 {
+    1
+}
+
+fn default_key_version_refresh() -> u64 {
+    300
+}
+
+impl SignerConfig {
+    /// Secret fields, resolved and protected by the service configuration.
+    pub fn secrets_mut(&mut self) -> Vec<&mut Secret> {
+        match self {
+            SignerConfig::PrivateKey { key } => vec![key],
+            SignerConfig::Mnemonic {
+                phrase, passphrase, ..
+            } => std::iter::once(phrase).chain(passphrase.as_mut()).collect(),
+            SignerConfig::KeyStore { password, .. } => vec![password],
+            SignerConfig::KeyStoreDir { password, .. } => password.iter_mut().collect(),
+            SignerConfig::AzureKeyVault { secret, .. } => vec![secret],
+            SignerConfig::AlicloudKms { secret, .. } => vec![secret],
+            SignerConfig::Pkcs11 { pin, .. } => vec![pin],
+            SignerConfig::VaultKv { vault, .. } | SignerConfig::VaultPlugin { vault, .. } => {
+                vault.secrets_mut()
+            }
+            SignerConfig::AwsKms { credentials, .. } => credentials
+                .iter_mut()
+                .flat_map(AwsCredentials::secrets_mut)
+                .collect(),
+            SignerConfig::GoogleKms { .. } => Vec::new(),
+            SignerConfig::Replicas { replicas, .. } => replicas
+                .iter_mut()
+                .flat_map(SignerConfig::secrets_mut)
+                .collect(),
+            SignerConfig::Sealed { token, secret, .. } => match secret {
+                SealedSecret::Mnemonic { passphrase, .. } => {
+                    std::iter::once(token).chain(passphrase.as_mut()).collect()
+                }
+                SealedSecret::PrivateKey => vec![token],
+            },
+        }
+    }
+
+    /// Short name of the signing backend, as used in the `type` tag.
+    pub fn backend(&self) -> &'static str {
+        match self {
+            SignerConfig::PrivateKey { .. } => "private_key",
+            SignerConfig::Mnemonic { .. } => "mnemonic",
+            SignerConfig::KeyStore { .. } => "key_store",
+            SignerConfig::KeyStoreDir { .. } => "key_store_dir",
+            SignerConfig::AzureKeyVault { .. } => "azure_key_vault",
+            SignerConfig::AwsKms { .. } => "aws_kms",
+            SignerConfig::GoogleKms { .. } => "google_kms",
+            SignerConfig::Replicas { .. } => "replicas",
+            SignerConfig::AlicloudKms { .. } => "alicloud_kms",
+            SignerConfig::VaultKv { .. } => "vault_kv",
+            SignerConfig::VaultPlugin { .. } => "vault_plugin",
+            SignerConfig::Pkcs11 { .. } => "pkcs11",
+            SignerConfig::Sealed { .. } => "sealed",
+        }
+    }
 }
 
-impl fmt::Debug for SignerConfig {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl SealedSecret {
+    /// Configuration of the signer that uses the reconstructed `secret`.
+    pub fn unsealed(&self, secret: Secret) -> SignerConfig {
         match self {
-            SignerConfig::PrivateKey(_) => f.write_str("PrivateKey(...)"),
-            SignerConfig::Mnemonic(_) => f.write_str("Mnemonic(...)"),
-            SignerConfig::KeyStore { path, password: _ } => f
-                .debug_struct("KeyStore")
-                .field("path", path)
-                .field("password", &"[hidden]")
-                .finish(),
-            SignerConfig::AwsKms { key } => f.debug_struct("AwsKms").field("key", key).finish(),
-            SignerConfig::GoogleKms {
-                project_id,
-                location,
-                key_ring,
-                key,
-                version,
-            } => f
-                .debug_struct("GoogleKms")
-                .field("project_id", project_id)
-                .field("location", location)
-                .field("key_ring", key_ring)
-                .field("key", key)
-                .field("version", version)
-                .finish(),
-            SignerConfig::AlicloudKms { key, secret: _ } => f
-                .debug_struct("AlicloudKms")
-                .field("key", key)
-                .field("secret", &"[hidden]")
-                .finish(),
-            SignerConfig::AzureKeyVault { key, secret: _ } => f
-                .debug_struct("AzureKeyVault")
-                .field("key", key)
-                .field("secret", &"[hidden]")
-                .finish(),
+            SealedSecret::PrivateKey => SignerConfig::PrivateKey { key: secret },
+            SealedSecret::Mnemonic {
+                passphrase,
+                language,
+                derivation_path,
+                index,
+                count,
+            } => SignerConfig::Mnemonic {
+                phrase: secret,
+                passphrase: passphrase.clone(),
+                language: *language,
+                derivation_path: derivation_path.clone(),
+                index: *index,
+                count: *count,
+            },
         }
     }
 }
@@ -0,0 +1,154 @@
+use alloy::signers::gcp::{
+    GcpKeyRingRef, GcpSigner, KeySpecifier,
+    gcloud_sdk::{
+        GCP_DEFAULT_SCOPES, GoogleApi, GoogleAuthMiddleware, TokenSourceType,
+        google::cloud::kms::v1::{
+            ListCryptoKeyVersionsRequest, crypto_key_version::CryptoKeyVersionState,
+            key_management_service_client::KeyManagementServiceClient,
+        },
+        tonic,
+    },
+};
+
+use super::retry::{Retry, RetrySigner};
+use crate::prelude::*;
+
+pub const DEFAULT_ENDPOINT: &str = "https://cloudkms.googleapis.com";
+
+pub type KmsClient = GoogleApi<KeyManagementServiceClient<GoogleAuthMiddleware>>;
+
+/// KMS client for `endpoint`, authenticated with the service account key in
+/// `credentials_file` or else the default Google credentials.
+pub async fn kms_client(
+    endpoint: Option<&str>,
+    credentials_file: Option<&str>,
+) -> Result<KmsClient> {
+    let token_source = match credentials_file {
+        Some(path) => TokenSourceType::File(path.into()),
+        None => TokenSourceType::Default,
+    };
+    Ok(GoogleApi::from_function_with_token_source(
+        KeyManagementServiceClient::new,
+        endpoint.unwrap_or(DEFAULT_ENDPOINT),
+        None,
+        GCP_DEFAULT_SCOPES.clone(),
+        token_source,
+    )
+    .await?)
+}
+
+/// Newest enabled version of a key, resolved again on every refresh so a
+/// rotated key takes over without a restart. Asymmetric keys have no primary
+/// version, so the highest enabled version number stands in for it.
+pub struct GcpLatestKey {
+    client: KmsClient,
+    keyring: GcpKeyRingRef,
+    key: String,
+    version: u64,
+    retry: Retry,
+}
+
+impl GcpLatestKey {
+    pub fn new(client: KmsClient, keyring: GcpKeyRingRef, key: &str, retry: Retry) -> Self {
+        GcpLatestKey {
+            client,
+            keyring,
+            key: key.to_string(),
+            version: 0,
+            retry,
+        }
+    }
+
+    /// Resource name of the key.
+    pub fn name(&self) -> String {
+        format!(
+            "projects/{}/locations/{}/keyRings/{}/cryptoKeys/{}",
+            self.keyring.google_project_id, self.keyring.location, self.keyring.name, self.key
+        )
+    }
+
+    /// Signer of the newest enabled version, if it changed since the last
+    /// refresh.
+    pub async fn refresh(&mut self) -> Result<Option<RetrySigner>> {
+        let this = &*self;
+        let version = self
+            .retry
+            .call("list_key_versions", || this.latest_enabled_version())
+            .await?;
+        if version == self.version {
+            return Ok(None);
+        }
+        let signer = self
+            .retry
+            .call("get_public_key", || {
+                let specifier = KeySpecifier::new(this.keyring.clone(), &this.key, version);
+                let signer = GcpSigner::new(this.client.clone(), specifier, None);
+                async { signer.await.map_err(|e| Box::new(e).into()) }
+            })
+            .await?;
+        self.version = version;
+        Ok(Some(RetrySigner::new(Box::new(signer), self.retry.clone())))
+    }
+
+    async fn latest_enabled_version(&self) -> Result<u64> {
+        let mut latest = None;
+        let mut page_token = String::new();
+        loop {
+            let request = ListCryptoKeyVersionsRequest {
+                parent: self.name(),
+                filter: "state=ENABLED".to_string(),
+                page_token,
+                ..Default::default()
+            };
+            let response = self
+                .client
+                .get()
+                .list_crypto_key_versions(traced(request))
+                .await
+                .map_err(Box::new)?
+                .into_inner();
+            latest = response
+                .crypto_key_versions
+                .iter()
+                .filter(|version| version.state == CryptoKeyVersionState::Enabled as i32)
+                .filter_map(|version| version_number(&version.name))
+                .chain(latest)
+                .max();
+            if response.next_page_token.is_empty() {
+                break;
+            }
+            page_token = response.next_page_token;
+        }
+        latest
+            .ok_or_else(|| Error::InvalidConfig(format!("{} has no enabled version", self.name())))
+    }
+}
+
+/// `message` as a request carrying the current trace context. Only calls made
+/// here can carry it: alloy's `GcpSigner` builds its own requests.
+fn traced<T>(message: T) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    for (name, value) in crate::otel::current_context_headers() {
+        let name = tonic::metadata::AsciiMetadataKey::from_bytes(name.as_bytes());
+        if let (Ok(name), Ok(value)) = (name, value.parse()) {
+            request.metadata_mut().insert(name, value);
+        }
+    }
+    request
+}
+
+fn version_number(name: &str) -> Option<u64> {
+    name.rsplit_once("/cryptoKeyVersions/")?.1.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_version_number() {
+        let name = "projects/p/locations/global/keyRings/r/cryptoKeys/k/cryptoKeyVersions/12";
+        assert_eq!(version_number(name), Some(12));
+        assert_eq!(version_number("projects/p/locations/global"), None);
+    }
+}
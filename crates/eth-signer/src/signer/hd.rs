@@ -0,0 +1,199 @@
+use alloy::{
+    primitives::B256,
+    signers::local::{
+        LocalSignerError, PrivateKeySigner,
+        coins_bip39::{Mnemonic, Wordlist},
+    },
+};
+use coins_bip32::{
+    enc::{MainnetEncoder, XKeyEncoder},
+    path::DerivationPath,
+    prelude::SigningKey,
+    xkeys::XPriv,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{MnemonicLanguage, WithWordlist};
+use crate::prelude::*;
+use crate::secret::Secret;
+
+/// Per-request child keys of one seed, for deposit-address style workloads.
+/// Only keys below `subtree` can be derived.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DerivationConfig {
+    pub source: DerivationSource,
+    /// Allowlisted subtree, e.g. `m/44'/60'/1'/0`. Requests name a child by
+    /// its index below it or by a full path inside it. For an `xprv` source
+    /// the path is relative to the extended key, `m` being the key itself.
+    pub subtree: String,
+    /// Largest child index that may be derived directly below `subtree`.
+    #[serde(default)]
+    pub max_index: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum DerivationSource {
+    Mnemonic {
+        /// Mnemonic phrase, or `file:<path>`.
+        phrase: Secret,
+        /// BIP-39 passphrase, or `file:<path>`.
+        #[serde(default)]
+        passphrase: Option<Secret>,
+        #[serde(default)]
+        language: MnemonicLanguage,
+    },
+    Xprv {
+        /// Base58 extended private key, or `file:<path>`.
+        key: Secret,
+    },
+}
+
+impl DerivationConfig {
+    pub fn secrets_mut(&mut self) -> Vec<&mut Secret> {
+        match &mut self.source {
+            DerivationSource::Mnemonic {
+                phrase, passphrase, ..
+            } => std::iter::once(phrase).chain(passphrase.as_mut()).collect(),
+            DerivationSource::Xprv { key } => vec![key],
+        }
+    }
+}
+
+/// Child key requested by a caller: an index directly below the subtree or a
+/// full derivation path inside it.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ChildKey {
+    Index(u32),
+    Path(String),
+}
+
+/// Extended key at the allowlisted subtree. The seed is stretched once at
+/// startup, so deriving a child per request is cheap.
+pub struct HdKeys {
+    root: XPriv,
+    subtree: DerivationPath,
+    max_index: Option<u32>,
+}
+
+impl HdKeys {
+    pub fn new(config: &DerivationConfig) -> Result<Self> {
+        let subtree: DerivationPath = config.subtree.parse().map_err(LocalSignerError::from)?;
+        let master = match &config.source {
+            DerivationSource::Mnemonic {
+                phrase,
+                passphrase,
+                language,
+            } => master_key(phrase, passphrase.as_ref(), *language)?,
+            DerivationSource::Xprv { key } => {
+                MainnetEncoder::xpriv_from_base58(key.expose()).map_err(LocalSignerError::from)?
+            }
+        };
+        Ok(HdKeys {
+            root: master
+                .derive_path(&subtree)
+                .map_err(LocalSignerError::from)?,
+            subtree,
+            max_index: config.max_index,
+        })
+    }
+
+    /// Derive the signer of `child`, returning its full derivation path.
+    pub fn derive(&self, child: &ChildKey) -> Result<(String, PrivateKeySigner)> {
+        let relative = match child {
+            ChildKey::Index(index) => DerivationPath::from(vec![*index]),
+            ChildKey::Path(path) => {
+                let path: DerivationPath = path
+                    .parse()
+                    .map_err(|_| Error::InvalidDerivationPath(path.clone()))?;
+                path.without_prefix(&self.subtree)
+                    .filter(|relative| !relative.is_empty())
+                    .ok_or_else(|| Error::InvalidDerivationPath(path.derivation_string()))?
+            }
+        };
+        let path = self
+            .subtree
+            .iter()
+            .chain(relative.iter())
+            .copied()
+            .collect::<DerivationPath>();
+        let first = relative.iter().next().copied().unwrap_or_default();
+        if self.max_index.is_some_and(|max| first > max) {
+            return Err(Error::InvalidDerivationPath(path.derivation_string()));
+        }
+
+        let child = self
+            .root
+            .derive_path(&relative)
+            .map_err(LocalSignerError::from)?;
+        let key: &SigningKey = child.as_ref();
+        let signer = PrivateKeySigner::from_bytes(&B256::from_slice(&key.to_bytes()))
+            .map_err(LocalSignerError::from)?;
+        Ok((path.derivation_string(), signer))
+    }
+}
+
+fn master_key(
+    phrase: &Secret,
+    passphrase: Option<&Secret>,
+    language: MnemonicLanguage,
+) -> Result<XPriv> {
+    struct MasterKey<'a> {
+        phrase: &'a Secret,
+        passphrase: Option<&'a Secret>,
+    }
+
+    impl WithWordlist for MasterKey<'_> {
+        type Output = Result<XPriv>;
+
+        fn run<W: Wordlist>(self) -> Self::Output {
+            let mnemonic = Mnemonic::<W>::new_from_phrase(self.phrase.expose())
+                .map_err(LocalSignerError::from)?;
+            Ok(mnemonic
+                .master_key(self.passphrase.map(Secret::expose))
+                .map_err(LocalSignerError::from)?)
+        }
+    }
+
+    language.with_wordlist(MasterKey { phrase, passphrase })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derive_children_in_subtree() {
+        let config: DerivationConfig = serde_json::from_str(
+            r#"{
+                "source": {"type": "mnemonic", "phrase": "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"},
+                "subtree": "m/44'/60'/0'/0",
+                "max_index": 10
+            }"#,
+        )
+        .unwrap();
+        let keys = HdKeys::new(&config).unwrap();
+
+        let (path, signer) = keys.derive(&ChildKey::Index(1)).unwrap();
+        assert_eq!(path, "m/44'/60'/0'/0/1");
+        assert_eq!(
+            signer.address().to_string(),
+            "0x6Fac4D18c912343BF86fa7049364Dd4E424Ab9C0"
+        );
+        let (_, same) = keys.derive(&ChildKey::Path(path)).unwrap();
+        assert_eq!(same.address(), signer.address());
+
+        assert!(keys.derive(&ChildKey::Index(11)).is_err());
+        assert!(
+            keys.derive(&ChildKey::Path("m/44'/60'/1'/0/1".to_string()))
+                .is_err()
+        );
+        assert!(
+            keys.derive(&ChildKey::Path("m/44'/60'/0'/0".to_string()))
+                .is_err()
+        );
+    }
+}
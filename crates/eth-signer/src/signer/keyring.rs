@@ -0,0 +1,585 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use alloy::{
+    eips::eip7702::{Authorization, SignedAuthorization},
+    network::{EthereumWallet, TxSigner},
+    primitives::Address,
+};
+use serde::Serialize;
+use tokio::task::JoinSet;
+
+use super::{
+    CircuitBreaker, GcpLatestKey, KeySigner, KeyStoreDir, Retry, Seal, SealStatus, SignerConfig,
+    VaultKv, authorization,
+};
+use crate::prelude::*;
+
+type SharedSigner = Arc<dyn KeySigner>;
+
+/// Backend whose keys change while the service runs.
+pub(super) enum Source {
+    /// Adds the keystores that appear in the directory. Shared with the
+    /// blocking task that scans it.
+    KeyStoreDir(Arc<Mutex<KeyStoreDir>>),
+    /// Replaces its key when the secret gets a new version.
+    VaultKv {
+        kv: VaultKv,
+        current: Option<Address>,
+    },
+    /// Replaces its key when a newer key version is enabled.
+    GoogleKms {
+        key: GcpLatestKey,
+        current: Option<Address>,
+    },
+}
+
+/// All configured signers, built at startup and selected per request by the
+/// transaction's `from`. Reloadable backends add or replace signers while
+/// running, and a sealed signer joins once it is unsealed.
+pub struct KeyRing {
+    keys: RwLock<Keys>,
+    /// Sources to reload, taken by [`KeyRing::watch`].
+    sources: Mutex<Vec<(Backend, Source, Duration)>>,
+    seal: Option<Seal>,
+    /// Latest probe of each key and when it ran. Held while probing, so
+    /// concurrent readiness checks share one round of probes.
+    probes: tokio::sync::Mutex<HashMap<Address, (Instant, KeyHealth)>>,
+}
+
+/// Readiness of one key, as reported by `/readyz`.
+#[derive(Serialize, Debug, Clone)]
+pub struct KeyHealth {
+    pub address: Address,
+    pub backend: &'static str,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Unix time in seconds of the probe, unset when keys are not probed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checked_at: Option<u64>,
+}
+
+/// Backend label of the reconstructed keys of a sealed signer.
+const SEALED_BACKEND: &str = "sealed";
+
+/// Backend name and, for remote backends, the circuit breakers of its
+/// replicas.
+type Backend = (&'static str, Vec<Arc<CircuitBreaker>>);
+
+struct Keys {
+    wallet: EthereumWallet,
+    /// Unset while the only signer is sealed.
+    default: Option<Address>,
+    signers: HashMap<Address, (Backend, SharedSigner)>,
+    addresses: Vec<Address>,
+}
+
+impl KeyRing {
+    pub async fn new(configs: &[SignerConfig], strict_permissions: bool) -> Result<Self> {
+        let mut signers: Vec<(Backend, SharedSigner)> = Vec::new();
+        let mut sources = Vec::new();
+        let mut seal = None;
+        for config in configs {
+            if let Some(sealed) = Seal::new(config)? {
+                if seal.replace(sealed).is_some() {
+                    return Err(Error::InvalidConfig(
+                        "only one sealed signer is supported".to_string(),
+                    ));
+                }
+                continue;
+            }
+            let retries = config.retries();
+            let backend = (
+                config.backend(),
+                retries.iter().map(Retry::breaker).collect(),
+            );
+            if let Some((mut source, interval)) =
+                config.reloadable(strict_permissions, &retries).await?
+            {
+                for signer in source.initial().await? {
+                    signers.push((backend.clone(), signer));
+                }
+                if !interval.is_zero() {
+                    sources.push((backend, source, interval));
+                }
+                continue;
+            }
+            for signer in config.signers(&retries).await? {
+                signers.push((backend.clone(), Arc::from(signer)));
+            }
+        }
+        if signers.is_empty() && seal.is_none() {
+            return Err(Error::RequireConfigKeyNotFound("signers"));
+        }
+        let default = signers.first().map(|(_, signer)| signer.address());
+
+        let mut keys = Keys {
+            wallet: EthereumWallet::default(),
+            default,
+            signers: HashMap::new(),
+            addresses: Vec::new(),
+        };
+        for (backend, signer) in signers {
+            let address = signer.address();
+            if keys.signers.insert(address, (backend, signer)).is_some() {
+                return Err(Error::DuplicateSigner(address));
+            }
+            keys.addresses.push(address);
+        }
+        keys.rebuild_wallet();
+        Ok(KeyRing {
+            keys: RwLock::new(keys),
+            sources: Mutex::new(sources),
+            seal,
+            probes: tokio::sync::Mutex::default(),
+        })
+    }
+
+    /// Reload the reloadable backends in the background.
+    pub fn watch(self: &Arc<Self>) {
+        let sources = std::mem::take(&mut *self.sources.lock().unwrap());
+        for (backend, mut source, interval) in sources {
+            let keys = Arc::clone(self);
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    let loaded = match source.load().await {
+                        Ok(loaded) => loaded,
+                        Err(e) => {
+                            tracing::warn!("reload {}: {}", source.name(), e);
+                            continue;
+                        }
+                    };
+                    for (signer, replaced) in loaded {
+                        keys.add(&backend, signer, replaced);
+                    }
+                }
+            });
+        }
+    }
+
+    /// Register a reloaded signer, taking the place of `replaced`.
+    fn add(&self, backend: &Backend, signer: SharedSigner, replaced: Option<Address>) {
+        let address = signer.address();
+        let mut keys = self.keys.write().unwrap();
+        if keys.signers.contains_key(&address) {
+            return;
+        }
+        let was_default = replaced.is_some() && replaced == keys.default;
+        match replaced.and_then(|old| keys.remove(&old).then_some(old)) {
+            Some(old) => tracing::info!("replaced {} signer {} with {}", backend.0, old, address),
+            None => tracing::info!("added {} signer {}", backend.0, address),
+        }
+        let position = if was_default || keys.default.is_none() {
+            keys.default = Some(address);
+            0
+        } else {
+            keys.addresses.len()
+        };
+        keys.signers.insert(address, (backend.clone(), signer));
+        keys.addresses.insert(position, address);
+        keys.rebuild_wallet();
+    }
+
+    pub fn wallet(&self) -> EthereumWallet {
+        self.keys.read().unwrap().wallet.clone()
+    }
+
+    /// Address used when a request does not name one.
+    pub fn default_address(&self) -> Option<Address> {
+        self.keys.read().unwrap().default
+    }
+
+    pub fn addresses(&self) -> Vec<Address> {
+        self.keys.read().unwrap().addresses.clone()
+    }
+
+    /// Resolve the signing address for `from`, falling back to the default.
+    /// Unknown addresses may belong to the sealed key while it is sealed.
+    pub fn resolve(&self, from: Option<Address>) -> Result<Address> {
+        let keys = self.keys.read().unwrap();
+        match from {
+            None => keys.default.ok_or(Error::Sealed),
+            Some(from) if keys.signers.contains_key(&from) => Ok(from),
+            Some(_) if self.is_sealed() => Err(Error::Sealed),
+            Some(from) => Err(Error::UnknownSigner(from)),
+        }
+    }
+
+    /// Whether a sealed signer is configured and has not been unsealed.
+    pub fn is_sealed(&self) -> bool {
+        self.seal.as_ref().is_some_and(Seal::is_sealed)
+    }
+
+    /// Unsealing progress, without a sealed signer `None`.
+    pub fn seal_status(&self) -> Option<SealStatus> {
+        self.seal.as_ref().map(Seal::status)
+    }
+
+    /// Whether `token` may unseal and seal the sealed signer.
+    pub fn authorize_seal(&self, token: &str) -> bool {
+        self.seal.as_ref().is_some_and(|seal| seal.authorize(token))
+    }
+
+    /// Submit an unseal share, adding the sealed signer's keys once enough
+    /// shares are in.
+    pub fn unseal(&self, share: &str) -> Result<SealStatus> {
+        let seal = self
+            .seal
+            .as_ref()
+            .ok_or(Error::RequireConfigKeyNotFound("sealed"))?;
+        if let Some(signers) = seal.submit(share)? {
+            let backend = (SEALED_BACKEND, Vec::new());
+            for signer in signers {
+                self.add(&backend, Arc::from(signer), None);
+            }
+        }
+        Ok(seal.status())
+    }
+
+    /// Remove the sealed signer's keys and discard submitted shares.
+    pub fn seal(&self) -> Result<SealStatus> {
+        let seal = self
+            .seal
+            .as_ref()
+            .ok_or(Error::RequireConfigKeyNotFound("sealed"))?;
+        let addresses = seal.seal();
+        if !addresses.is_empty() {
+            let mut keys = self.keys.write().unwrap();
+            for address in &addresses {
+                keys.remove(address);
+            }
+            keys.rebuild_wallet();
+            tracing::info!("sealed signers {:?}", addresses);
+        }
+        Ok(seal.status())
+    }
+
+    /// Sign an EIP-7702 authorization with the key of `from`.
+    pub async fn sign_authorization(
+        &self,
+        from: Address,
+        authorization: Authorization,
+    ) -> Result<SignedAuthorization> {
+        let signer = {
+            let keys = self.keys.read().unwrap();
+            let (_, signer) = keys.signers.get(&from).ok_or(Error::UnknownSigner(from))?;
+            signer.clone()
+        };
+        authorization::sign(&*signer, authorization).await
+    }
+
+    /// Short name of the backend holding `address`.
+    pub fn backend(&self, address: &Address) -> &'static str {
+        let keys = self.keys.read().unwrap();
+        keys.signers
+            .get(address)
+            .map_or("unknown", |((backend, _), _)| backend)
+    }
+
+    /// Readiness of every key. Each key's backend is checked at most once
+    /// per `max_age`, without signing; a zero `max_age` skips probes. Keys whose backend fails
+    /// fast after repeated errors, on every replica, are never ready.
+    pub async fn health(&self, max_age: Duration) -> Vec<KeyHealth> {
+        let mut probes = self.probes.lock().await;
+        let keys: Vec<_> = {
+            let keys = self.keys.read().unwrap();
+            keys.addresses
+                .iter()
+                .map(|address| {
+                    let ((backend, breakers), signer) = &keys.signers[address];
+                    let open = !breakers.is_empty() && breakers.iter().all(|b| b.is_open());
+                    (*address, *backend, Arc::clone(signer), open)
+                })
+                .collect()
+        };
+        probes.retain(|address, _| keys.iter().any(|(a, ..)| a == address));
+
+        let mut running = JoinSet::new();
+        let mut tasks = HashMap::new();
+        for (address, backend, signer, open) in &keys {
+            let fresh = probes
+                .get(address)
+                .is_some_and(|(at, _)| at.elapsed() < max_age);
+            if *open || fresh || max_age.is_zero() {
+                continue;
+            }
+            let (address, backend, signer) = (*address, *backend, Arc::clone(signer));
+            let task = running.spawn(async move {
+                let result = signer.check().await;
+                if let Err(e) = &result {
+                    tracing::warn!("probe of {} signer {} failed: {}", backend, address, e);
+                }
+                KeyHealth {
+                    address,
+                    backend,
+                    ok: result.is_ok(),
+                    error: result.err().map(|e| e.to_string()),
+                    checked_at: Some(unix_now()),
+                }
+            });
+            tasks.insert(task.id(), (address, backend));
+        }
+        while let Some(joined) = running.join_next_with_id().await {
+            let health = match joined {
+                Ok((_, health)) => health,
+                Err(e) => {
+                    let (address, backend) = tasks[&e.id()];
+                    tracing::error!("probe of {} signer {} panicked: {}", backend, address, e);
+                    KeyHealth {
+                        address,
+                        backend,
+                        ok: false,
+                        error: Some(e.to_string()),
+                        checked_at: Some(unix_now()),
+                    }
+                }
+            };
+            probes.insert(health.address, (Instant::now(), health));
+        }
+
+        keys.into_iter()
+            .map(|(address, backend, _, open)| {
+                let probed = probes.get(&address).filter(|_| !max_age.is_zero());
+                match probed {
+                    _ if open => KeyHealth {
+                        address,
+                        backend,
+                        ok: false,
+                        error: Some(Error::CircuitOpen(backend).to_string()),
+                        checked_at: probed.and_then(|(_, health)| health.checked_at),
+                    },
+                    Some((_, health)) => health.clone(),
+                    None => KeyHealth {
+                        address,
+                        backend,
+                        ok: true,
+                        error: None,
+                        checked_at: None,
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+/// Unix time in seconds.
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl Keys {
+    fn remove(&mut self, address: &Address) -> bool {
+        self.addresses.retain(|a| a != address);
+        if self.default == Some(*address) {
+            self.default = self.addresses.first().copied();
+        }
+        self.signers.remove(address).is_some()
+    }
+
+    /// The wallet cannot drop signers, so it is rebuilt on every change.
+    fn rebuild_wallet(&mut self) {
+        let mut wallet = EthereumWallet::default();
+        for address in &self.addresses {
+            let signer = Arc::clone(&self.signers[address].1);
+            if Some(*address) == self.default {
+                wallet.register_default_signer(signer);
+            } else {
+                wallet.register_signer(signer);
+            }
+        }
+        self.wallet = wallet;
+    }
+}
+
+impl Source {
+    /// Keys added or changed since the last load, each with the address it
+    /// replaces.
+    async fn load(&mut self) -> Result<Vec<(SharedSigner, Option<Address>)>> {
+        let (signer, current): (SharedSigner, _) = match self {
+            // Decrypting keystores is deliberately slow.
+            Source::KeyStoreDir(directory) => {
+                let directory = Arc::clone(directory);
+                let scan = move || directory.lock().expect("lock poisoned").scan();
+                return Ok(tokio::task::spawn_blocking(scan)
+                    .await
+                    .expect("keystore scan panicked")?
+                    .into_iter()
+                    .map(|signer| (Arc::new(signer) as SharedSigner, None))
+                    .collect());
+            }
+            Source::VaultKv { kv, current } => match kv.refresh().await? {
+                Some(signer) => (Arc::new(signer), current),
+                None => return Ok(Vec::new()),
+            },
+            Source::GoogleKms { key, current } => match key.refresh().await? {
+                Some(signer) => (Arc::new(signer), current),
+                None => return Ok(Vec::new()),
+            },
+        };
+        let replaced = current.replace(signer.address());
+        Ok(vec![(signer, replaced)])
+    }
+
+    /// Keys present at startup. Versioned sources must resolve their current
+    /// key here, so the service does not start without it.
+    async fn initial(&mut self) -> Result<Vec<SharedSigner>> {
+        let (signer, current): (SharedSigner, _) = match self {
+            Source::KeyStoreDir(_) => {
+                return Ok(self
+                    .load()
+                    .await?
+                    .into_iter()
+                    .map(|(signer, _)| signer)
+                    .collect());
+            }
+            Source::VaultKv { kv, current } => (
+                Arc::new(kv.refresh().await?.ok_or_else(|| {
+                    Error::InvalidConfig(format!("vault secret {} has no key", kv.location()))
+                })?),
+                current,
+            ),
+            Source::GoogleKms { key, current } => (
+                Arc::new(key.refresh().await?.ok_or_else(|| {
+                    Error::InvalidConfig(format!(
+                        "google kms key {} has no enabled version",
+                        key.name()
+                    ))
+                })?),
+                current,
+            ),
+        };
+        *current = Some(signer.address());
+        Ok(vec![signer])
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Source::KeyStoreDir(directory) => {
+                let directory = directory.lock().expect("lock poisoned");
+                format!("keystore directory {}", directory.path().display())
+            }
+            Source::VaultKv { kv, .. } => format!("vault secret {}", kv.location()),
+            Source::GoogleKms { key, .. } => format!("google kms key {}", key.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::{
+        primitives::Signature,
+        signers::local::{LocalSigner, PrivateKeySigner},
+    };
+
+    /// `#[tokio::test]` runs on a current-thread runtime, where blocking
+    /// inside a task panics.
+    #[tokio::test]
+    async fn load_keystore_dir_on_current_thread() {
+        let dir = std::env::temp_dir().join(format!("eth-signer-ring-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut rng = rand::thread_rng();
+        let (signer, _) =
+            LocalSigner::encrypt_keystore(&dir, &mut rng, [4u8; 32], "pw", Some("a")).unwrap();
+        let config = serde_json::json!({
+            "type": "keystore_dir",
+            "path": dir,
+            "password": "pw",
+            "watch_interval_secs": 0,
+        });
+        let config: SignerConfig = serde_json::from_value(config).unwrap();
+
+        let keys = KeyRing::new(&[config], false).await.unwrap();
+        assert_eq!(keys.addresses(), [signer.address()]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Signer whose probe panics.
+    struct Panicking(Address);
+
+    #[async_trait::async_trait]
+    impl TxSigner<Signature> for Panicking {
+        fn address(&self) -> Address {
+            self.0
+        }
+
+        async fn sign_transaction(
+            &self,
+            _tx: &mut dyn alloy::consensus::SignableTransaction<Signature>,
+        ) -> alloy::signers::Result<Signature> {
+            unreachable!()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl KeySigner for Panicking {
+        async fn check(&self) -> Result<()> {
+            panic!("probe panicked")
+        }
+
+        async fn sign_digest(&self, _hash: &alloy::primitives::B256) -> Result<Signature> {
+            unreachable!()
+        }
+    }
+
+    #[tokio::test]
+    async fn record_panicked_probes() {
+        let local = PrivateKeySigner::random();
+        let panicking = Address::repeat_byte(1);
+        let signers: [(_, SharedSigner); 2] = [
+            (panicking, Arc::new(Panicking(panicking))),
+            (local.address(), Arc::new(local.clone())),
+        ];
+        let mut keys = Keys {
+            wallet: EthereumWallet::default(),
+            default: Some(panicking),
+            signers: HashMap::new(),
+            addresses: Vec::new(),
+        };
+        for (address, signer) in signers {
+            keys.signers.insert(address, (("test", Vec::new()), signer));
+            keys.addresses.push(address);
+        }
+        let keys = KeyRing {
+            keys: RwLock::new(keys),
+            sources: Mutex::default(),
+            seal: None,
+            probes: tokio::sync::Mutex::default(),
+        };
+
+        let health = keys.health(Duration::from_secs(60)).await;
+        assert!(!health[0].ok);
+        assert!(health[1].ok);
+        assert!(health.iter().all(|h| h.checked_at.is_some()));
+    }
+
+    #[tokio::test]
+    async fn probe_keys() {
+        let config: SignerConfig = serde_json::from_str(
+            r#"{"type": "private_key", "key": "a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d2e3f4a5b6c7d8e9f0a1b2"}"#,
+        )
+        .unwrap();
+        let keys = KeyRing::new(&[config], false).await.unwrap();
+
+        let health = keys.health(Duration::from_secs(60)).await;
+        assert!(health[0].ok);
+        let checked_at = health[0].checked_at.expect("probed");
+        // Reused until it is older than `max_age`.
+        assert_eq!(
+            keys.health(Duration::from_secs(60)).await[0].checked_at,
+            Some(checked_at)
+        );
+        assert_eq!(keys.health(Duration::ZERO).await[0].checked_at, None);
+    }
+}
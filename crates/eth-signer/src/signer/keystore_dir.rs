@@ -0,0 +1,177 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use alloy::{
+    primitives::Address,
+    signers::local::{LocalSigner, PrivateKeySigner},
+};
+
+use crate::prelude::*;
+use crate::secret::{self, Secret};
+
+/// Directory of V3 keystore files, like geth's `keystore` directory. Each
+/// account is unlocked with its own password file, named after the address,
+/// or else the shared password.
+pub struct KeyStoreDir {
+    path: PathBuf,
+    password: Option<Secret>,
+    password_dir: Option<PathBuf>,
+    strict_permissions: bool,
+    /// Modification time of every file already tried and of its password
+    /// file, so a keystore is decrypted again only when one of them changed
+    /// or its password file appeared.
+    seen: HashMap<PathBuf, (SystemTime, Option<SystemTime>)>,
+}
+
+impl KeyStoreDir {
+    pub fn new(
+        path: &str,
+        password: Option<&Secret>,
+        password_dir: Option<&str>,
+        strict_permissions: bool,
+    ) -> Self {
+        KeyStoreDir {
+            path: PathBuf::from(path),
+            password: password.cloned(),
+            password_dir: password_dir.map(PathBuf::from),
+            strict_permissions,
+            seen: HashMap::new(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load the keystores added or changed since the last scan. Files that
+    /// cannot be unlocked are logged and skipped, so one bad file does not
+    /// hide the others.
+    pub fn scan(&mut self) -> Result<Vec<PrivateKeySigner>> {
+        let mut signers = Vec::new();
+        for entry in std::fs::read_dir(&self.path)? {
+            let entry = entry?;
+            let path = entry.path();
+            let metadata = entry.metadata()?;
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if !metadata.is_file() || hidden {
+                continue;
+            }
+            let stamp = (metadata.modified()?, self.password_modified(&path));
+            if self.seen.insert(path.clone(), stamp) == Some(stamp) {
+                continue;
+            }
+            match self.load(&path) {
+                Ok(signer) => signers.push(signer),
+                Err(e) => tracing::warn!("skip keystore {}: {}", path.display(), e),
+            }
+        }
+        Ok(signers)
+    }
+
+    fn load(&self, path: &Path) -> Result<PrivateKeySigner> {
+        let recorded = recorded_address(path)?;
+        let password = match self.password_file(recorded) {
+            Some(file) => secret::read_file(file, self.strict_permissions)?,
+            None => self
+                .password
+                .clone()
+                .ok_or(Error::RequireConfigKeyNotFound("password"))?,
+        };
+        let signer = LocalSigner::decrypt_keystore(path, password.expose())?;
+        match recorded {
+            Some(recorded) if recorded != signer.address() => Err(Error::KeystoreAddressMismatch(
+                path.display().to_string(),
+                recorded,
+            )),
+            Some(_) => Ok(signer),
+            None => {
+                tracing::warn!(
+                    "keystore {} records no address, loaded {}",
+                    path.display(),
+                    signer.address()
+                );
+                Ok(signer)
+            }
+        }
+    }
+
+    /// Modification time of the password file of the keystore at `path`.
+    fn password_modified(&self, path: &Path) -> Option<SystemTime> {
+        let file = self.password_file(recorded_address(path).ok()?)?;
+        std::fs::metadata(file).and_then(|m| m.modified()).ok()
+    }
+
+    /// Password file of `address`, named `<address>` with or without `0x`.
+    fn password_file(&self, address: Option<Address>) -> Option<PathBuf> {
+        let (dir, address) = (self.password_dir.as_ref()?, address?);
+        let hex = alloy::hex::encode(address);
+        [format!("0x{hex}"), hex]
+            .into_iter()
+            .map(|name| dir.join(name))
+            .find(|file| file.is_file())
+    }
+}
+
+/// Address recorded in the keystore JSON, if any.
+fn recorded_address(path: &Path) -> Result<Option<Address>> {
+    let keystore: serde_json::Value = serde_json::from_slice(&std::fs::read(path)?)?;
+    match keystore.get("address").and_then(|a| a.as_str()) {
+        Some(address) => Ok(Some(address.parse::<Address>().map_err(|_| {
+            Error::InvalidConfig(format!("invalid address in keystore {}", path.display()))
+        })?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_keystore_dir() {
+        let dir = std::env::temp_dir().join(format!("eth-signer-keystores-{}", std::process::id()));
+        let passwords = dir.join("passwords");
+        std::fs::create_dir_all(&passwords).unwrap();
+
+        let mut rng = rand::thread_rng();
+        let (shared, _) =
+            LocalSigner::encrypt_keystore(&dir, &mut rng, [1u8; 32], "shared", Some("a")).unwrap();
+        let (own, _) =
+            LocalSigner::encrypt_keystore(&dir, &mut rng, [2u8; 32], "own", Some("b")).unwrap();
+        std::fs::write(passwords.join(alloy::hex::encode(own.address())), "own\n").unwrap();
+
+        let password = Secret::new("shared".to_string());
+        let mut keystores = KeyStoreDir::new(
+            dir.to_str().unwrap(),
+            Some(&password),
+            passwords.to_str(),
+            false,
+        );
+        let mut loaded: Vec<Address> = keystores
+            .scan()
+            .unwrap()
+            .iter()
+            .map(|s| s.address())
+            .collect();
+        loaded.sort();
+        let mut expected = vec![shared.address(), own.address()];
+        expected.sort();
+        assert_eq!(loaded, expected);
+        assert!(keystores.scan().unwrap().is_empty(), "unchanged files");
+
+        // A keystore whose password file is missing is tried again once it
+        // appears.
+        let (late, _) =
+            LocalSigner::encrypt_keystore(&dir, &mut rng, [3u8; 32], "late", Some("c")).unwrap();
+        assert!(keystores.scan().unwrap().is_empty());
+        std::fs::write(passwords.join(alloy::hex::encode(late.address())), "late").unwrap();
+        let loaded = keystores.scan().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].address(), late.address());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
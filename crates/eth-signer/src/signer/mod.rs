@@ -1,87 +1,481 @@
+mod authorization;
+mod aws;
 mod config;
-pub use config::SignerConfig;
+mod gcp;
+mod hd;
+mod keyring;
+mod keystore_dir;
+mod pkcs11;
+mod replicas;
+mod retry;
+mod seal;
+mod vault;
+pub use aws::{AwsAssumeRole, AwsCredentials};
+pub use config::{DEFAULT_DERIVATION_PATH, MnemonicLanguage, SealedSecret, SignerConfig};
+pub use hd::{ChildKey, DerivationConfig, HdKeys};
+pub use keyring::{KeyHealth, KeyRing};
+pub use keystore_dir::KeyStoreDir;
+pub use retry::{CircuitBreaker, RetryConfig};
+pub use seal::{SealStatus, split_secret};
+pub use vault::VaultConfig;
+
+use std::{sync::Arc, time::Duration};
+
+use gcp::GcpLatestKey;
+use keyring::Source;
+use pkcs11::{Pkcs11Signer, TokenSelector};
+use replicas::ReplicaSigner;
+use retry::{Retry, RetrySigner};
+use seal::Seal;
+use vault::{VaultClient, VaultKv, VaultPluginSigner};
 
 use crate::prelude::*;
+use crate::secret::Secret;
 use alloy::{
-    network::{EthereumWallet, TxSigner},
-    primitives::{Address, Signature},
+    consensus::SignableTransaction,
+    network::TxSigner,
+    primitives::{Address, B256, ChainId, Signature},
     signers::{
-        aws::{AwsSigner, aws_config, aws_sdk_kms},
-        gcp::{
-            GcpKeyRingRef, GcpSigner, KeySpecifier,
-            gcloud_sdk::{
-                GoogleApi,
-                google::cloud::kms::v1::key_management_service_client::KeyManagementServiceClient,
+        Signer,
+        aws::AwsSigner,
+        gcp::{GcpKeyRingRef, GcpSigner, KeySpecifier},
+        local::{
+            LocalSigner, MnemonicBuilder, PrivateKeySigner,
+            coins_bip39::{
+                ChineseSimplified, ChineseTraditional, Czech, English, French, Italian, Japanese,
+                Korean, Portuguese, Spanish, Wordlist,
             },
         },
-        local::{LocalSigner, MnemonicBuilder, PrivateKeySigner, coins_bip39::English},
+        utils::public_key_to_address,
     },
 };
 
+type BoxSigner = Box<dyn KeySigner>;
+type RemoteSigner = Box<dyn RemoteKey>;
+
+/// Signer held by the key ring.
+#[async_trait::async_trait]
+trait KeySigner: TxSigner<Signature> + Send + Sync + 'static {
+    /// Check that the backend still serves the key, without signing.
+    async fn check(&self) -> Result<()>;
+
+    /// Sign a hash that is not a transaction's, such as an EIP-7702
+    /// authorization's.
+    async fn sign_digest(&self, hash: &B256) -> Result<Signature>;
+
+    /// Chain the key is bound to, if any.
+    fn bound_chain_id(&self) -> Option<ChainId> {
+        None
+    }
+}
+
+/// Single remote KMS key.
+#[async_trait::async_trait]
+trait RemoteKey: Signer<Signature> + Send + Sync + 'static {
+    /// Address of the public key the KMS now holds for the key.
+    async fn public_address(&self) -> Result<Address>;
+
+    /// Check that the KMS still holds the key, without signing.
+    async fn check(&self) -> Result<()> {
+        let found = self.public_address().await?;
+        if found != self.address() {
+            return Err(Error::InvalidConfig(format!(
+                "key of {} now has address {found}",
+                self.address()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl KeySigner for PrivateKeySigner {
+    /// The key is held in memory.
+    async fn check(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn sign_digest(&self, hash: &B256) -> Result<Signature> {
+        Ok(Signer::sign_hash(self, hash).await?)
+    }
+
+    fn bound_chain_id(&self) -> Option<ChainId> {
+        Signer::chain_id(self)
+    }
+}
+
+#[async_trait::async_trait]
+impl RemoteKey for AwsSigner {
+    async fn public_address(&self) -> Result<Address> {
+        let key = self.get_pubkey().await.map_err(Box::new)?;
+        Ok(public_key_to_address(&key))
+    }
+}
+
+#[async_trait::async_trait]
+impl RemoteKey for GcpSigner {
+    async fn public_address(&self) -> Result<Address> {
+        let key = self.get_pubkey().await.map_err(Box::new)?;
+        Ok(public_key_to_address(&key))
+    }
+}
+
 impl SignerConfig {
-    async fn signer(&self) -> Result<Box<dyn TxSigner<Signature> + Send + Sync + 'static>> {
-        let signer: Box<dyn TxSigner<Signature> + Send + Sync + 'static> = match self {
-            SignerConfig::PrivateKey(key) => Box::new(key.parse::<PrivateKeySigner>()?),
-            SignerConfig::Mnemonic(mnemonic) => Box::new(
-                MnemonicBuilder::<English>::default()
-                    .phrase(mnemonic)
-                    .build()?,
-            ),
+    /// Retry policies of a remote backend, one per replica. All signers of a
+    /// backend share them.
+    fn retries(&self) -> Vec<Retry> {
+        match self {
+            SignerConfig::AwsKms { retry, .. } | SignerConfig::GoogleKms { retry, .. } => {
+                vec![Retry::new(retry, self.backend())]
+            }
+            SignerConfig::Replicas { replicas, .. } => {
+                replicas.iter().flat_map(SignerConfig::retries).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Build the signers of this backend. A mnemonic yields one signer per
+    /// derived account, every other backend exactly one. Remote backends run
+    /// their calls under `retries`, or else under policies of their own.
+    async fn signers(&self, retries: &[Retry]) -> Result<Vec<BoxSigner>> {
+        let retries = match retries {
+            [] => self.retries(),
+            retries => retries.to_vec(),
+        };
+        let signer: BoxSigner = match self {
+            SignerConfig::PrivateKey { .. } | SignerConfig::Mnemonic { .. } => {
+                return self.local_signers();
+            }
             SignerConfig::KeyStore { path, password } => {
-                Box::new(LocalSigner::decrypt_keystore(path, password)?)
+                Box::new(LocalSigner::decrypt_keystore(path, password.expose())?)
+            }
+            SignerConfig::AwsKms { .. }
+            | SignerConfig::GoogleKms {
+                version: Some(_), ..
+            } => {
+                let retry = retries[0].clone();
+                Box::new(RetrySigner::new(self.remote_signer(&retry).await?, retry))
+            }
+            SignerConfig::Replicas {
+                replicas,
+                hedge_after_ms,
+            } => {
+                // Every aws_kms and google_kms replica has one retry policy.
+                if retries.len() != replicas.len() {
+                    return Err(Error::InvalidConfig(
+                        "replicas must be aws_kms or google_kms signers".to_string(),
+                    ));
+                }
+                let mut built = Vec::with_capacity(replicas.len());
+                for (replica, retry) in replicas.iter().zip(retries) {
+                    built.push((replica.remote_signer(&retry).await?, retry));
+                }
+                let hedge_after = hedge_after_ms.map(Duration::from_millis);
+                Box::new(ReplicaSigner::new(built, hedge_after)?)
+            }
+            SignerConfig::VaultPlugin { vault, mount, key } => {
+                let client = Arc::new(VaultClient::new(vault)?);
+                Box::new(VaultPluginSigner::new(client, mount, key).await?)
+            }
+            SignerConfig::Pkcs11 {
+                module,
+                slot,
+                token_label,
+                pin,
+                key_label,
+                sessions,
+            } => {
+                let token = match (slot, token_label) {
+                    (Some(slot), None) => TokenSelector::Slot(*slot),
+                    (None, Some(label)) => TokenSelector::Label(label),
+                    _ => {
+                        return Err(Error::InvalidConfig(
+                            "pkcs11 signer needs exactly one of slot and token_label".to_string(),
+                        ));
+                    }
+                };
+                Box::new(Pkcs11Signer::new(module, token, pin, key_label, *sessions)?)
+            }
+            // Loaded and reloaded by the key ring, see `reloadable`, or
+            // unsealed at runtime.
+            SignerConfig::Sealed { .. }
+            | SignerConfig::KeyStoreDir { .. }
+            | SignerConfig::VaultKv { .. }
+            | SignerConfig::GoogleKms { version: None, .. } => {
+                return Ok(Vec::new());
+            }
+            SignerConfig::AzureKeyVault { .. } | SignerConfig::AlicloudKms { .. } => {
+                return Err(Error::InvalidConfig(format!(
+                    "{} signers are not supported",
+                    self.backend()
+                )));
+            }
+        };
+        Ok(vec![signer])
+    }
+
+    /// Signers of a private key or mnemonic, which need no I/O.
+    fn local_signers(&self) -> Result<Vec<BoxSigner>> {
+        match self {
+            SignerConfig::PrivateKey { key } => {
+                Ok(vec![Box::new(key.expose().parse::<PrivateKeySigner>()?)])
             }
-            SignerConfig::AwsKms { key } => {
-                let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
-                let client = aws_sdk_kms::Client::new(&config);
-                Box::new(
-                    AwsSigner::new(client, key.clone(), Some(1))
-                        .await
-                        .map_err(Box::new)?,
-                )
+            SignerConfig::Mnemonic {
+                phrase,
+                passphrase,
+                language,
+                derivation_path,
+                index,
+                count,
+            } => {
+                let end = index
+                    .checked_add(*count)
+                    .ok_or_else(|| Error::InvalidConfig("mnemonic index overflow".to_string()))?;
+                (*index..end)
+                    .map(|i| {
+                        let path = format!("{}/{i}", derivation_path.trim_end_matches('/'));
+                        let signer =
+                            derive_mnemonic(phrase, passphrase.as_ref(), *language, &path)?;
+                        Ok(Box::new(signer) as BoxSigner)
+                    })
+                    .collect()
+            }
+            _ => Err(Error::InvalidConfig(format!(
+                "{} signer is not a local key",
+                self.backend()
+            ))),
+        }
+    }
+
+    /// Signer of a single remote KMS key, its public key fetched under
+    /// `retry`. Only these backends can be replicas.
+    async fn remote_signer(&self, retry: &Retry) -> Result<RemoteSigner> {
+        match self {
+            SignerConfig::AwsKms {
+                key,
+                alias,
+                region,
+                endpoint,
+                profile,
+                credentials,
+                assume_role,
+                chain_id,
+                ..
+            } => {
+                let key_id = aws::key_id(key.as_deref(), alias.as_deref())?;
+                let client = aws::kms_client(aws::KmsClientOptions {
+                    region: region.as_deref(),
+                    endpoint: endpoint.as_deref(),
+                    profile: profile.as_deref(),
+                    credentials: credentials.as_ref(),
+                    assume_role: assume_role.as_ref(),
+                })
+                .await;
+                let chain_id = (*chain_id != 0).then_some(*chain_id);
+                let signer = retry
+                    .call("get_public_key", || {
+                        let signer = AwsSigner::new(client.clone(), key_id.clone(), chain_id);
+                        async { signer.await.map_err(|e| Box::new(e).into()) }
+                    })
+                    .await?;
+                Ok(Box::new(signer))
             }
-            Self::GoogleKms {
+            SignerConfig::GoogleKms {
                 project_id,
                 location,
                 key_ring,
                 key,
-                version,
+                version: Some(version),
+                endpoint,
+                credentials_file,
+                ..
             } => {
                 let keyring_ref = GcpKeyRingRef::new(project_id, location, key_ring);
-
-                let client = GoogleApi::from_function(
-                    KeyManagementServiceClient::new,
-                    "https://cloudkms.googleapis.com",
-                    None,
-                )
-                .await?;
-                let key_specifier = KeySpecifier::new(keyring_ref, key, *version);
-
-                Box::new(
-                    GcpSigner::new(client, key_specifier, None)
-                        .await
-                        .map_err(Box::new)?,
-                )
+                let client =
+                    gcp::kms_client(endpoint.as_deref(), credentials_file.as_deref()).await?;
+                let signer = retry
+                    .call("get_public_key", || {
+                        let specifier = KeySpecifier::new(keyring_ref.clone(), key, *version);
+                        let signer = GcpSigner::new(client.clone(), specifier, None);
+                        async { signer.await.map_err(|e| Box::new(e).into()) }
+                    })
+                    .await?;
+                Ok(Box::new(signer))
             }
-            _ => unimplemented!(),
-        };
-        Ok(signer)
+            SignerConfig::GoogleKms { version: None, .. } => Err(Error::InvalidConfig(
+                "google_kms replicas need a fixed version".to_string(),
+            )),
+            _ => Err(Error::InvalidConfig(format!(
+                "{} signer cannot be a replica, only aws_kms and google_kms",
+                self.backend()
+            ))),
+        }
     }
 
-    pub async fn wallet(&self) -> Result<EthereumWallet> {
-        let signer = self.signer().await?;
-        Ok(EthereumWallet::new(signer))
+    /// Source of a backend whose keys change while the service runs, and
+    /// the interval between reloads.
+    async fn reloadable(
+        &self,
+        strict_permissions: bool,
+        retries: &[Retry],
+    ) -> Result<Option<(Source, Duration)>> {
+        Ok(Some(match self {
+            SignerConfig::KeyStoreDir {
+                path,
+                password,
+                password_dir,
+                watch_interval_secs,
+            } => (
+                Source::KeyStoreDir(Arc::new(std::sync::Mutex::new(KeyStoreDir::new(
+                    path,
+                    password.as_ref(),
+                    password_dir.as_deref(),
+                    strict_permissions,
+                )))),
+                Duration::from_secs(*watch_interval_secs),
+            ),
+            SignerConfig::VaultKv {
+                vault,
+                mount,
+                path,
+                field,
+                refresh_interval_secs,
+            } => (
+                Source::VaultKv {
+                    kv: VaultKv::new(Arc::new(VaultClient::new(vault)?), mount, path, field),
+                    current: None,
+                },
+                Duration::from_secs(*refresh_interval_secs),
+            ),
+            SignerConfig::GoogleKms {
+                project_id,
+                location,
+                key_ring,
+                key,
+                version: None,
+                endpoint,
+                credentials_file,
+                refresh_interval_secs,
+                retry,
+            } => (
+                Source::GoogleKms {
+                    key: GcpLatestKey::new(
+                        gcp::kms_client(endpoint.as_deref(), credentials_file.as_deref()).await?,
+                        GcpKeyRingRef::new(project_id, location, key_ring),
+                        key,
+                        match retries {
+                            [retry, ..] => retry.clone(),
+                            [] => Retry::new(retry, self.backend()),
+                        },
+                    ),
+                    current: None,
+                },
+                Duration::from_secs(*refresh_interval_secs),
+            ),
+            _ => return Ok(None),
+        }))
     }
+}
 
-    pub async fn address(&self) -> Result<Address> {
-        let signer = self.signer().await?;
-        Ok(signer.address())
+/// Low-s signature of `hash` by `address` from the `r || s` returned by a
+/// remote signer, with the recovery id found by recovery since remote
+/// signers disagree on whether and how they encode `v`.
+fn recover_signature(bytes: &[u8], hash: &B256, address: Address) -> Result<Signature> {
+    if bytes.len() != 64 && bytes.len() != 65 {
+        return Err(Error::InvalidSignature(format!(
+            "{} bytes, expected 64 or 65",
+            bytes.len()
+        )));
     }
+    let signature = Signature::from_bytes_and_parity(&bytes[..64], false).normalized_s();
+    [false, true]
+        .into_iter()
+        .map(|parity| signature.with_parity(parity))
+        .find(|candidate| candidate.recover_address_from_prehash(hash).ok() == Some(address))
+        .ok_or_else(|| Error::InvalidSignature(format!("does not recover to {address}")))
+}
+
+/// Restrict `tx` to the signer's chain, filling it in when unset, as alloy's
+/// own signers do before signing the hash.
+fn apply_chain_id(
+    chain_id: Option<ChainId>,
+    tx: &mut dyn SignableTransaction<Signature>,
+) -> alloy::signers::Result<()> {
+    match chain_id {
+        Some(chain_id) if !tx.set_chain_id_checked(chain_id) => {
+            Err(alloy::signers::Error::TransactionChainIdMismatch {
+                signer: chain_id,
+                tx: tx.chain_id().unwrap_or_default(),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Work generic over the wordlist of a mnemonic.
+trait WithWordlist {
+    type Output;
+
+    fn run<W: Wordlist>(self) -> Self::Output;
+}
+
+impl MnemonicLanguage {
+    /// Run `work` with this language's wordlist.
+    fn with_wordlist<T: WithWordlist>(self, work: T) -> T::Output {
+        match self {
+            MnemonicLanguage::English => work.run::<English>(),
+            MnemonicLanguage::ChineseSimplified => work.run::<ChineseSimplified>(),
+            MnemonicLanguage::ChineseTraditional => work.run::<ChineseTraditional>(),
+            MnemonicLanguage::Czech => work.run::<Czech>(),
+            MnemonicLanguage::French => work.run::<French>(),
+            MnemonicLanguage::Italian => work.run::<Italian>(),
+            MnemonicLanguage::Japanese => work.run::<Japanese>(),
+            MnemonicLanguage::Korean => work.run::<Korean>(),
+            MnemonicLanguage::Portuguese => work.run::<Portuguese>(),
+            MnemonicLanguage::Spanish => work.run::<Spanish>(),
+        }
+    }
+}
+
+fn derive_mnemonic(
+    phrase: &Secret,
+    passphrase: Option<&Secret>,
+    language: MnemonicLanguage,
+    path: &str,
+) -> Result<PrivateKeySigner> {
+    struct Derive<'a> {
+        phrase: &'a Secret,
+        passphrase: Option<&'a Secret>,
+        path: &'a str,
+    }
+
+    impl WithWordlist for Derive<'_> {
+        type Output = Result<PrivateKeySigner>;
+
+        fn run<W: Wordlist>(self) -> Self::Output {
+            let mut builder = MnemonicBuilder::<W>::default()
+                .phrase(self.phrase.expose())
+                .derivation_path(self.path)?;
+            if let Some(passphrase) = self.passphrase {
+                builder = builder.password(passphrase.expose());
+            }
+            Ok(builder.build()?)
+        }
+    }
+
+    language.with_wordlist(Derive {
+        phrase,
+        passphrase,
+        path,
+    })
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use alloy::signers::SignerSync;
 
     #[test]
     fn parase_private_key() {
@@ -93,6 +487,46 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn unsupported_backends_fail_to_build() {
+        let config: SignerConfig =
+            serde_json::from_str(r#"{"type": "azure_key_vault", "key": "k", "secret": "s"}"#)
+                .unwrap();
+        assert!(matches!(
+            config.signers(&[]).await,
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn derive_mnemonic_accounts() {
+        let config: SignerConfig = serde_json::from_str(
+            r#"{"type": "mnemonic", "phrase": "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about", "count": 2}"#,
+        )
+        .unwrap();
+        let signers = config.signers(&[]).await.unwrap();
+        let addresses: Vec<String> = signers.iter().map(|s| s.address().to_string()).collect();
+        assert_eq!(
+            addresses,
+            [
+                "0x9858EfFD232B4033E47d90003D41EC34EcaEda94",
+                "0x6Fac4D18c912343BF86fa7049364Dd4E424Ab9C0"
+            ]
+        );
+
+        let with_passphrase = derive_mnemonic(
+            &Secret::new("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about".to_string()),
+            Some(&Secret::new("TREZOR".to_string())),
+            MnemonicLanguage::English,
+            "m/44'/60'/0'/0/0",
+        )
+        .unwrap();
+        assert_ne!(
+            with_passphrase.address().to_string(),
+            "0x9858EfFD232B4033E47d90003D41EC34EcaEda94"
+        );
+    }
+
     #[test]
     fn parse_mnemonic() {
         let s = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
@@ -105,4 +539,25 @@ mod tests {
             "0x9858EfFD232B4033E47d90003D41EC34EcaEda94"
         );
     }
+
+    #[test]
+    fn recover_remote_signature() {
+        let signer: PrivateKeySigner =
+            "a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d2e3f4a5b6c7d8e9f0a1b2"
+                .parse()
+                .unwrap();
+        let hash = B256::repeat_byte(7);
+        let expected = signer.sign_hash_sync(&hash).unwrap();
+        // Without `v`, and the high-s twin some signers return.
+        let order: alloy::primitives::U256 =
+            "0xfffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141"
+                .parse()
+                .unwrap();
+        let high_s = Signature::new(expected.r(), order - expected.s(), !expected.v());
+        for bytes in [&expected.as_bytes()[..64], &high_s.as_bytes()[..]] {
+            let recovered = recover_signature(bytes, &hash, signer.address()).unwrap();
+            assert_eq!(recovered, expected);
+        }
+        assert!(recover_signature(&[0], &hash, signer.address()).is_err());
+    }
 }
@@ -0,0 +1,240 @@
+use std::sync::{Arc, Mutex};
+
+use alloy::{
+    consensus::SignableTransaction,
+    network::TxSigner,
+    primitives::{Address, B256, Signature},
+};
+use cryptoki::{
+    context::{CInitializeArgs, Pkcs11},
+    error::{Error as CryptokiError, RvError},
+    mechanism::Mechanism,
+    object::{Attribute, AttributeType, ObjectClass, ObjectHandle},
+    session::{Session, SessionState, UserType},
+    slot::Slot,
+    types::AuthPin,
+};
+use tokio::sync::Semaphore;
+
+use super::{KeySigner, recover_signature};
+use crate::prelude::*;
+use crate::secret::Secret;
+
+/// secp256k1 key in a PKCS#11 token, signed with `CKM_ECDSA` over the
+/// transaction hash.
+pub struct Pkcs11Signer {
+    pool: Arc<SessionPool>,
+    key: ObjectHandle,
+    address: Address,
+}
+
+/// Sessions reused across requests. A token allows one operation per
+/// session at a time, so each signature borrows a session of its own.
+struct SessionPool {
+    context: Pkcs11,
+    slot: Slot,
+    idle: Mutex<Vec<Session>>,
+    /// Bounds the sessions open at once.
+    permits: Semaphore,
+    /// Kept open so the token stays logged in while pooled sessions are
+    /// closed after errors.
+    _login: Mutex<Session>,
+}
+
+/// Token holding the key, by slot ID or token label.
+pub enum TokenSelector<'a> {
+    Slot(u64),
+    Label(&'a str),
+}
+
+impl Pkcs11Signer {
+    pub fn new(
+        module: &str,
+        token: TokenSelector<'_>,
+        pin: &Secret,
+        key_label: &str,
+        sessions: usize,
+    ) -> Result<Self> {
+        let context = Pkcs11::new(module)?;
+        match context.initialize(CInitializeArgs::OsThreads) {
+            // Another signer already initialized the same module.
+            Err(CryptokiError::Pkcs11(RvError::CryptokiAlreadyInitialized, _)) => {}
+            result => result?,
+        }
+        let slot = find_slot(&context, token)?;
+
+        let login = context.open_ro_session(slot)?;
+        match login.login(UserType::User, Some(&AuthPin::new(pin.expose().into()))) {
+            Err(CryptokiError::Pkcs11(RvError::UserAlreadyLoggedIn, _)) => {}
+            result => result?,
+        }
+        let key = find_object(&login, ObjectClass::PRIVATE_KEY, key_label)?;
+        let public_key = find_object(&login, ObjectClass::PUBLIC_KEY, key_label)?;
+        let point = match login
+            .get_attributes(public_key, &[AttributeType::EcPoint])?
+            .pop()
+        {
+            Some(Attribute::EcPoint(point)) => point,
+            _ => {
+                return Err(Error::InvalidConfig(format!(
+                    "PKCS#11 public key '{key_label}' has no EC point"
+                )));
+            }
+        };
+        let address = address_of(&point)?;
+
+        Ok(Pkcs11Signer {
+            pool: Arc::new(SessionPool {
+                context,
+                slot,
+                idle: Mutex::new(Vec::new()),
+                permits: Semaphore::new(sessions.max(1)),
+                _login: Mutex::new(login),
+            }),
+            key,
+            address,
+        })
+    }
+
+    async fn sign_hash(&self, hash: &B256) -> Result<Signature> {
+        let _permit = self.pool.permits.acquire().await.expect("never closed");
+        let (pool, key, hash_bytes) = (Arc::clone(&self.pool), self.key, *hash);
+        // PKCS#11 calls block, often on a network round trip to the HSM.
+        let bytes = tokio::task::spawn_blocking(move || pool.sign(key, &hash_bytes))
+            .await
+            .expect("PKCS#11 signing panicked")?;
+        recover_signature(&bytes, hash, self.address)
+    }
+
+    async fn check_token(&self) -> Result<()> {
+        let _permit = self.pool.permits.acquire().await.expect("never closed");
+        let (pool, key) = (Arc::clone(&self.pool), self.key);
+        tokio::task::spawn_blocking(move || pool.check(key))
+            .await
+            .expect("PKCS#11 check panicked")
+    }
+}
+
+impl SessionPool {
+    fn sign(&self, key: ObjectHandle, hash: &B256) -> Result<Vec<u8>> {
+        self.with_session(|session| Ok(session.sign(&Mechanism::Ecdsa, key, hash.as_slice())?))
+    }
+
+    /// Whether the token is still logged in and holds the key, read with
+    /// `C_GetSessionInfo` and `C_GetAttributeValue`.
+    fn check(&self, key: ObjectHandle) -> Result<()> {
+        self.with_session(|session| {
+            let state = session.get_session_info()?.session_state();
+            if state != SessionState::RoUser {
+                return Err(Error::InvalidConfig(format!(
+                    "PKCS#11 session is {state:?}, not logged in"
+                )));
+            }
+            session.get_attributes(key, &[AttributeType::Class])?;
+            Ok(())
+        })
+    }
+
+    fn with_session<T>(&self, call: impl FnOnce(&Session) -> Result<T>) -> Result<T> {
+        let idle = self.idle.lock().unwrap().pop();
+        let session = match idle {
+            Some(session) => session,
+            None => self.context.open_ro_session(self.slot)?,
+        };
+        let result = call(&session)?;
+        // Sessions that failed are dropped, which closes them.
+        self.idle.lock().unwrap().push(session);
+        Ok(result)
+    }
+}
+
+fn find_slot(context: &Pkcs11, token: TokenSelector<'_>) -> Result<Slot> {
+    let slots = context.get_slots_with_token()?;
+    let found = match token {
+        TokenSelector::Slot(id) => slots.into_iter().find(|slot| slot.id() == id),
+        TokenSelector::Label(label) => slots.into_iter().find(|slot| {
+            context
+                .get_token_info(*slot)
+                .is_ok_and(|info| info.label() == label)
+        }),
+    };
+    found.ok_or_else(|| Error::InvalidConfig("PKCS#11 token not found".to_string()))
+}
+
+fn find_object(session: &Session, class: ObjectClass, label: &str) -> Result<ObjectHandle> {
+    let template = [
+        Attribute::Class(class),
+        Attribute::Label(label.as_bytes().to_vec()),
+    ];
+    let mut objects = session.find_objects(&template)?;
+    match (objects.pop(), objects.is_empty()) {
+        (Some(object), true) => Ok(object),
+        (Some(_), false) => Err(Error::InvalidConfig(format!(
+            "PKCS#11 label '{label}' matches more than one {class}"
+        ))),
+        (None, _) => Err(Error::InvalidConfig(format!(
+            "PKCS#11 {class} '{label}' not found"
+        ))),
+    }
+}
+
+/// Address of a `CKA_EC_POINT`, which tokens return either raw or wrapped in
+/// a DER OCTET STRING.
+fn address_of(point: &[u8]) -> Result<Address> {
+    // A raw uncompressed point starts with 0x04 as well, but is 65 bytes.
+    let point = match point {
+        [0x04, len, rest @ ..] if point.len() != 65 && *len as usize == rest.len() => rest,
+        raw => raw,
+    };
+    let key = alloy::signers::k256::ecdsa::VerifyingKey::from_sec1_bytes(point)
+        .map_err(|e| Error::InvalidConfig(format!("PKCS#11 EC point: {e}")))?;
+    Ok(alloy::signers::utils::public_key_to_address(&key))
+}
+
+#[async_trait::async_trait]
+impl TxSigner<Signature> for Pkcs11Signer {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> alloy::signers::Result<Signature> {
+        self.sign_hash(&tx.signature_hash())
+            .await
+            .map_err(alloy::signers::Error::other)
+    }
+}
+
+#[async_trait::async_trait]
+impl KeySigner for Pkcs11Signer {
+    async fn check(&self) -> Result<()> {
+        self.check_token().await
+    }
+
+    async fn sign_digest(&self, hash: &B256) -> Result<Signature> {
+        self.sign_hash(hash).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::signers::local::PrivateKeySigner;
+
+    #[test]
+    fn ec_point_address() {
+        let signer: PrivateKeySigner =
+            "a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d2e3f4a5b6c7d8e9f0a1b2"
+                .parse()
+                .unwrap();
+        let key = signer.credential().verifying_key();
+        let raw = key.to_encoded_point(false).as_bytes().to_vec();
+        let der = [&[0x04, 0x41][..], &raw].concat();
+        let compressed = key.to_encoded_point(true).as_bytes().to_vec();
+        for point in [raw, der, compressed] {
+            assert_eq!(address_of(&point).unwrap(), signer.address());
+        }
+    }
+}
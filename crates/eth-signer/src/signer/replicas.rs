@@ -0,0 +1,269 @@
+use std::{sync::Arc, time::Duration};
+
+use alloy::{
+    consensus::SignableTransaction,
+    network::TxSigner,
+    primitives::{Address, B256, ChainId, Signature},
+};
+use tokio::task::JoinSet;
+use tracing::Instrument;
+
+use super::retry::Retry;
+use super::{KeySigner, RemoteSigner, apply_chain_id};
+use crate::prelude::*;
+
+/// One key behind several KMS replicas. The replicas are asked in order: the
+/// next one when the previous one failed, or when it has not answered within
+/// the hedging delay. The first signature wins and the slower requests are
+/// cancelled.
+pub struct ReplicaSigner {
+    replicas: Vec<Arc<Replica>>,
+    address: Address,
+    chain_id: Option<ChainId>,
+    hedge_after: Option<Duration>,
+}
+
+struct Replica {
+    signer: RemoteSigner,
+    retry: Retry,
+}
+
+impl ReplicaSigner {
+    /// Fails unless every replica signs as the same address for the same
+    /// chain.
+    pub fn new(
+        replicas: Vec<(RemoteSigner, Retry)>,
+        hedge_after: Option<Duration>,
+    ) -> Result<Self> {
+        let Some((first, _)) = replicas.first() else {
+            return Err(Error::InvalidConfig(
+                "replicas signer needs at least one replica".to_string(),
+            ));
+        };
+        let (address, chain_id) = (first.address(), first.chain_id());
+        for (index, (signer, _)) in replicas.iter().enumerate() {
+            if signer.address() != address {
+                return Err(Error::InvalidConfig(format!(
+                    "replica {index} signs as {}, replica 0 as {address}",
+                    signer.address()
+                )));
+            }
+            if signer.chain_id() != chain_id {
+                return Err(Error::InvalidConfig(format!(
+                    "replica {index} signs for chain {:?}, replica 0 for {chain_id:?}",
+                    signer.chain_id()
+                )));
+            }
+        }
+        Ok(ReplicaSigner {
+            replicas: replicas
+                .into_iter()
+                .map(|(signer, retry)| Arc::new(Replica { signer, retry }))
+                .collect(),
+            address,
+            chain_id,
+            hedge_after,
+        })
+    }
+
+    async fn sign_hash(&self, hash: B256) -> Result<Signature> {
+        // Dropping the set cancels the requests still running.
+        let mut pending = JoinSet::new();
+        let mut started = 0;
+        let mut last_error = None;
+        self.start(&mut pending, &mut started, hash);
+        loop {
+            let hedge = self.hedge_after.filter(|_| started < self.replicas.len());
+            let joined = match hedge {
+                Some(delay) => tokio::select! {
+                    joined = pending.join_next() => joined,
+                    _ = tokio::time::sleep(delay) => {
+                        tracing::info!("replica {} is slow, asking replica {}", started - 1, started);
+                        self.start(&mut pending, &mut started, hash);
+                        continue;
+                    }
+                },
+                None => pending.join_next().await,
+            };
+            match joined {
+                Some(Ok((_, Ok(signature)))) => return Ok(signature),
+                Some(Ok((index, Err(e)))) => {
+                    tracing::warn!("replica {} failed: {}", index, e);
+                    last_error = Some(e);
+                    self.start(&mut pending, &mut started, hash);
+                }
+                Some(Err(e)) => std::panic::resume_unwind(e.into_panic()),
+                None => return Err(last_error.expect("at least one replica was asked")),
+            }
+        }
+    }
+
+    /// Ask the next replica not asked yet, if any.
+    fn start(
+        &self,
+        pending: &mut JoinSet<(usize, Result<Signature>)>,
+        started: &mut usize,
+        hash: B256,
+    ) {
+        let Some(replica) = self.replicas.get(*started) else {
+            return;
+        };
+        let (index, replica) = (*started, Arc::clone(replica));
+        let span = tracing::info_span!("replica", index);
+        pending.spawn(async move { (index, replica.sign(hash).await) }.instrument(span));
+        *started += 1;
+    }
+}
+
+impl Replica {
+    async fn sign(&self, hash: B256) -> Result<Signature> {
+        let signer = &self.signer;
+        self.retry
+            .call(
+                "sign",
+                move || async move { Ok(signer.sign_hash(&hash).await?) },
+            )
+            .await
+    }
+}
+
+#[async_trait::async_trait]
+impl TxSigner<Signature> for ReplicaSigner {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> alloy::signers::Result<Signature> {
+        apply_chain_id(self.chain_id, tx)?;
+        self.sign_hash(tx.signature_hash())
+            .await
+            .map_err(alloy::signers::Error::other)
+    }
+}
+
+#[async_trait::async_trait]
+impl KeySigner for ReplicaSigner {
+    /// Ready while any replica still holds the key.
+    async fn check(&self) -> Result<()> {
+        let mut last_error = None;
+        for replica in &self.replicas {
+            let signer = &replica.signer;
+            match replica
+                .retry
+                .call("get_public_key", || signer.check())
+                .await
+            {
+                Ok(()) => return Ok(()),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.expect("at least one replica"))
+    }
+
+    async fn sign_digest(&self, hash: &B256) -> Result<Signature> {
+        self.sign_hash(*hash).await
+    }
+
+    fn bound_chain_id(&self) -> Option<ChainId> {
+        self.chain_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::signers::{Signer, local::PrivateKeySigner};
+
+    use super::*;
+    use crate::signer::{RemoteKey, RetryConfig};
+
+    /// Replica that never answers, or fails at once.
+    struct Unhealthy {
+        address: Address,
+        fail: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl Signer for Unhealthy {
+        async fn sign_hash(&self, _hash: &B256) -> alloy::signers::Result<Signature> {
+            if self.fail {
+                return Err(alloy::signers::Error::message("down"));
+            }
+            std::future::pending().await
+        }
+
+        fn address(&self) -> Address {
+            self.address
+        }
+
+        fn chain_id(&self) -> Option<ChainId> {
+            None
+        }
+
+        fn set_chain_id(&mut self, _chain_id: Option<ChainId>) {}
+    }
+
+    #[async_trait::async_trait]
+    impl RemoteKey for Unhealthy {
+        async fn public_address(&self) -> Result<Address> {
+            match self.fail {
+                true => Err(Error::InvalidConfig("down".to_string())),
+                false => Ok(self.address),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl RemoteKey for PrivateKeySigner {
+        async fn public_address(&self) -> Result<Address> {
+            Ok(self.address())
+        }
+    }
+
+    fn replica(signer: impl RemoteKey) -> (RemoteSigner, Retry) {
+        let config = RetryConfig {
+            timeout_ms: 60_000,
+            max_attempts: 1,
+            ..Default::default()
+        };
+        (Box::new(signer), Retry::new(&config, "test"))
+    }
+
+    #[tokio::test]
+    async fn fail_over_and_hedge() {
+        let local = PrivateKeySigner::random();
+        let address = local.address();
+        let hash = B256::repeat_byte(1);
+        let expected = local.sign_hash(&hash).await.unwrap();
+
+        let failing = Unhealthy {
+            address,
+            fail: true,
+        };
+        let signer =
+            ReplicaSigner::new(vec![replica(failing), replica(local.clone())], None).unwrap();
+        assert_eq!(signer.sign_hash(hash).await.unwrap(), expected);
+        // Ready while one replica holds the key, checked without signing.
+        assert!(signer.check().await.is_ok());
+        let down = Unhealthy {
+            address,
+            fail: true,
+        };
+        let signer = ReplicaSigner::new(vec![replica(down)], None).unwrap();
+        assert!(signer.check().await.is_err());
+
+        let stalled = Unhealthy {
+            address,
+            fail: false,
+        };
+        let hedge = Some(Duration::from_millis(10));
+        let signer =
+            ReplicaSigner::new(vec![replica(stalled), replica(local.clone())], hedge).unwrap();
+        assert_eq!(signer.sign_hash(hash).await.unwrap(), expected);
+
+        let other = PrivateKeySigner::random();
+        assert!(ReplicaSigner::new(vec![replica(local), replica(other)], None).is_err());
+    }
+}
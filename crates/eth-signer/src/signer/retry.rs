@@ -0,0 +1,477 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    consensus::SignableTransaction,
+    network::TxSigner,
+    primitives::{Address, B256, ChainId, Signature},
+    signers::{
+        aws::{
+            AwsSignerError,
+            aws_sdk_kms::{error::ProvideErrorMetadata, error::SdkError},
+        },
+        gcp::{
+            GcpSignerError,
+            gcloud_sdk::{
+                error::ErrorKind,
+                tonic::{Code, Status},
+            },
+        },
+    },
+};
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::Instrument;
+
+use super::{KeySigner, RemoteSigner, apply_chain_id};
+use crate::metrics::metrics;
+use crate::prelude::*;
+
+/// Timeouts, retries and circuit breaking of calls to a remote KMS.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    /// Time limit of a single call in milliseconds.
+    pub timeout_ms: u64,
+    /// Calls made before giving up, including the first one.
+    pub max_attempts: u32,
+    /// Base delay before the first retry in milliseconds, doubled on every
+    /// further retry. The actual delay is drawn at random up to this bound.
+    pub backoff_ms: u64,
+    /// Upper bound of the delay between retries in milliseconds.
+    pub max_backoff_ms: u64,
+    /// Consecutive failed calls that open the circuit, 0 disables.
+    pub breaker_threshold: u32,
+    /// Seconds an open circuit fails fast before letting one call probe the
+    /// backend.
+    pub breaker_cooldown_secs: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 5_000,
+            max_attempts: 3,
+            backoff_ms: 100,
+            max_backoff_ms: 2_000,
+            breaker_threshold: 5,
+            breaker_cooldown_secs: 30,
+        }
+    }
+}
+
+/// Runs calls to one backend under its [`RetryConfig`]. Clones share the
+/// circuit breaker.
+#[derive(Clone)]
+pub struct Retry {
+    config: RetryConfig,
+    backend: &'static str,
+    breaker: Arc<CircuitBreaker>,
+}
+
+/// Fails calls fast after `threshold` consecutive failures, until the
+/// cooldown has passed. One call then probes the backend while the others
+/// keep failing fast: success closes the circuit, failure opens it again.
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+    /// Whether a call is probing the backend after the cooldown.
+    probing: bool,
+}
+
+/// A call let through by a [`CircuitBreaker`]. A probe frees its place when
+/// dropped, even when the call is cancelled, so another call can probe.
+struct Admission<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+}
+
+impl Retry {
+    pub fn new(config: &RetryConfig, backend: &'static str) -> Self {
+        Retry {
+            config: config.clone(),
+            backend,
+            breaker: Arc::new(CircuitBreaker {
+                threshold: config.breaker_threshold,
+                cooldown: Duration::from_secs(config.breaker_cooldown_secs),
+                state: Mutex::new(BreakerState::default()),
+            }),
+        }
+    }
+
+    pub fn breaker(&self) -> Arc<CircuitBreaker> {
+        Arc::clone(&self.breaker)
+    }
+
+    /// Run `attempt` until it succeeds, fails with an error that repeating
+    /// cannot fix, or runs out of attempts. Every attempt gets its own span.
+    pub async fn call<T, F, Fut>(&self, operation: &'static str, mut attempt: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let Some(_admission) = self.breaker.admit() else {
+            return Err(Error::CircuitOpen(self.backend));
+        };
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        let max_attempts = self.config.max_attempts.max(1);
+        let mut number = 1;
+        let result = loop {
+            let span = tracing::info_span!(
+                "backend_call",
+                backend = self.backend,
+                operation,
+                attempt = number,
+                error = tracing::field::Empty,
+            );
+            let result = match tokio::time::timeout(timeout, attempt())
+                .instrument(span.clone())
+                .await
+            {
+                Ok(result) => result,
+                Err(_) => Err(Error::Timeout(self.backend, timeout)),
+            };
+            let error = match result {
+                Ok(value) => break Ok(value),
+                Err(e) => e,
+            };
+            span.record("error", tracing::field::display(&error));
+            if number >= max_attempts || !is_transient(&error) {
+                break Err(error);
+            }
+            let delay = self.backoff(number);
+            tracing::warn!(
+                backend = self.backend,
+                operation,
+                attempt = number,
+                "{}, retrying in {:?}",
+                error,
+                delay
+            );
+            metrics().record_retry(self.backend, operation);
+            tokio::time::sleep(delay).await;
+            number += 1;
+        };
+        match &result {
+            Ok(_) => self.breaker.success(),
+            // Rejections such as a wrong chain ID say nothing about the
+            // backend's health.
+            Err(e) if is_transient(e) => self.breaker.failure(self.backend),
+            Err(_) => {}
+        }
+        result
+    }
+
+    /// Delay before retry `number`, drawn uniformly up to the exponential
+    /// bound so that clients retrying together spread out.
+    fn backoff(&self, number: u32) -> Duration {
+        let bound = self
+            .config
+            .backoff_ms
+            .saturating_mul(1 << (number - 1).min(20))
+            .min(self.config.max_backoff_ms);
+        Duration::from_millis(rand::thread_rng().gen_range(0..=bound))
+    }
+}
+
+impl CircuitBreaker {
+    /// Let a call through unless the circuit is open. Once the cooldown has
+    /// passed, a single call is let through as a probe.
+    fn admit(&self) -> Option<Admission<'_>> {
+        let mut state = self.state.lock().expect("lock poisoned");
+        if state.is_open() {
+            return None;
+        }
+        let probe = state.open_until.is_some();
+        state.probing = probe;
+        Some(Admission {
+            breaker: self,
+            probe,
+        })
+    }
+
+    fn success(&self) {
+        *self.state.lock().expect("lock poisoned") = BreakerState::default();
+    }
+
+    fn failure(&self, backend: &'static str) {
+        let mut state = self.state.lock().expect("lock poisoned");
+        state.failures += 1;
+        if self.threshold > 0 && state.failures >= self.threshold {
+            if state.open_until.is_none() {
+                tracing::error!(
+                    "{} circuit opened after {} failed calls",
+                    backend,
+                    state.failures
+                );
+            }
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+
+    /// Whether calls currently fail fast.
+    pub fn is_open(&self) -> bool {
+        self.state.lock().expect("lock poisoned").is_open()
+    }
+}
+
+impl BreakerState {
+    /// Open until the cooldown has passed, and then while a probe runs.
+    fn is_open(&self) -> bool {
+        self.open_until
+            .is_some_and(|until| self.probing || Instant::now() < until)
+    }
+}
+
+impl Drop for Admission<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            self.breaker.state.lock().expect("lock poisoned").probing = false;
+        }
+    }
+}
+
+/// Whether a failed call may succeed when repeated: timeouts, throttling,
+/// server errors and transport failures.
+fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::Timeout(..) => true,
+        Error::AwsSigner(e) => is_transient_aws(e),
+        Error::GcpSigner(e) => is_transient_gcp(e),
+        Error::Grpc(status) => is_transient_status(status),
+        Error::AlloySigner(e) => is_transient_signer(e),
+        _ => false,
+    }
+}
+
+/// Signers box backend errors, sometimes more than once.
+fn is_transient_signer(error: &alloy::signers::Error) -> bool {
+    let alloy::signers::Error::Other(e) = error else {
+        return false;
+    };
+    if let Some(e) = e.downcast_ref::<AwsSignerError>() {
+        is_transient_aws(e)
+    } else if let Some(e) = e.downcast_ref::<GcpSignerError>() {
+        is_transient_gcp(e)
+    } else if let Some(e) = e.downcast_ref::<alloy::signers::Error>() {
+        is_transient_signer(e)
+    } else {
+        false
+    }
+}
+
+fn is_transient_aws(error: &AwsSignerError) -> bool {
+    fn sdk<E: ProvideErrorMetadata, R>(error: &SdkError<E, R>) -> bool {
+        match error {
+            SdkError::TimeoutError(_)
+            | SdkError::DispatchFailure(_)
+            | SdkError::ResponseError(_) => true,
+            SdkError::ServiceError(_) => {
+                matches!(
+                    error.code(),
+                    Some(
+                        "ThrottlingException"
+                            | "TooManyRequestsException"
+                            | "RequestLimitExceeded"
+                            | "KMSInternalException"
+                            | "DependencyTimeoutException"
+                            | "InternalFailure"
+                            | "ServiceUnavailable"
+                    )
+                )
+            }
+            _ => false,
+        }
+    }
+    match error {
+        AwsSignerError::Sign(e) => sdk(e),
+        AwsSignerError::GetPublicKey(e) => sdk(e),
+        _ => false,
+    }
+}
+
+fn is_transient_gcp(error: &GcpSignerError) -> bool {
+    match error {
+        GcpSignerError::RequestError(status) => is_transient_status(status),
+        GcpSignerError::GoogleKmsError(e) => {
+            matches!(e.kind(), ErrorKind::GrpcStatus(_) | ErrorKind::Http(_))
+        }
+        _ => false,
+    }
+}
+
+fn is_transient_status(status: &Status) -> bool {
+    matches!(
+        status.code(),
+        Code::Unavailable
+            | Code::DeadlineExceeded
+            | Code::ResourceExhausted
+            | Code::Aborted
+            | Code::Internal
+    )
+}
+
+/// Signer whose calls run under a [`Retry`].
+pub struct RetrySigner {
+    inner: RemoteSigner,
+    retry: Retry,
+}
+
+impl RetrySigner {
+    pub fn new(inner: RemoteSigner, retry: Retry) -> Self {
+        RetrySigner { inner, retry }
+    }
+}
+
+#[async_trait::async_trait]
+impl TxSigner<Signature> for RetrySigner {
+    fn address(&self) -> Address {
+        self.inner.address()
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> alloy::signers::Result<Signature> {
+        apply_chain_id(self.inner.chain_id(), tx)?;
+        self.sign_digest(&tx.signature_hash())
+            .await
+            .map_err(alloy::signers::Error::other)
+    }
+}
+
+#[async_trait::async_trait]
+impl KeySigner for RetrySigner {
+    async fn check(&self) -> Result<()> {
+        let inner = &self.inner;
+        self.retry.call("get_public_key", || inner.check()).await
+    }
+
+    async fn sign_digest(&self, hash: &B256) -> Result<Signature> {
+        let inner = &self.inner;
+        self.retry
+            .call(
+                "sign",
+                move || async move { Ok(inner.sign_hash(hash).await?) },
+            )
+            .await
+    }
+
+    fn bound_chain_id(&self) -> Option<ChainId> {
+        self.inner.chain_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    fn unavailable() -> Error {
+        // Boxed twice, as a signer returns it.
+        let error = GcpSignerError::RequestError(Status::unavailable("down"));
+        alloy::signers::Error::other(alloy::signers::Error::other(error)).into()
+    }
+
+    #[tokio::test]
+    async fn retry_transient_errors() {
+        let retry = Retry::new(
+            &RetryConfig {
+                backoff_ms: 1,
+                breaker_threshold: 2,
+                ..Default::default()
+            },
+            "test",
+        );
+        let calls = &AtomicU32::new(0);
+        let result = retry
+            .call("sign", || async move {
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(unavailable()),
+                    _ => Ok(7),
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), 7);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Permanent errors are not repeated and leave the circuit closed.
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<()> = retry
+            .call("sign", || async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(Error::InvalidSignature("bad".to_string()))
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        for _ in 0..2 {
+            let _: Result<()> = retry.call("sign", || async { Err(unavailable()) }).await;
+        }
+        assert!(retry.breaker().is_open());
+        let result: Result<()> = retry.call("sign", || async { Ok(()) }).await;
+        assert!(matches!(result, Err(Error::CircuitOpen("test"))));
+    }
+
+    #[test]
+    fn probe_once_after_cooldown() {
+        let retry = Retry::new(
+            &RetryConfig {
+                breaker_threshold: 1,
+                breaker_cooldown_secs: 0,
+                ..Default::default()
+            },
+            "test",
+        );
+        let breaker = retry.breaker();
+        breaker.failure("test");
+
+        // Only one call probes; the others fail fast until it settles.
+        let probe = breaker.admit().expect("cooldown passed");
+        assert!(breaker.admit().is_none());
+        assert!(breaker.is_open());
+        // A cancelled probe lets the next call probe.
+        drop(probe);
+        let probe = breaker.admit().expect("probe was cancelled");
+        breaker.success();
+        drop(probe);
+        assert!(!breaker.is_open());
+        let (first, second) = (breaker.admit(), breaker.admit());
+        assert!(first.is_some() && second.is_some());
+    }
+
+    #[tokio::test]
+    async fn time_out_attempts() {
+        let retry = Retry::new(
+            &RetryConfig {
+                timeout_ms: 10,
+                max_attempts: 2,
+                backoff_ms: 1,
+                ..Default::default()
+            },
+            "test",
+        );
+        let calls = &AtomicU32::new(0);
+        let result: Result<()> = retry
+            .call("sign", || async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                std::future::pending().await
+            })
+            .await;
+        assert!(matches!(result, Err(Error::Timeout("test", _))));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,227 @@
+use std::sync::Mutex;
+
+use alloy::primitives::Address;
+use blahaj::{Share, Sharks};
+use serde::Serialize;
+use zeroize::Zeroizing;
+
+use super::{BoxSigner, SealedSecret, SignerConfig};
+use crate::prelude::*;
+use crate::secret::Secret;
+
+/// Key of a `sealed` signer. Operators submit Shamir shares of its secret,
+/// which is reconstructed in memory once enough arrive and dropped as soon
+/// as its signers are built.
+pub(super) struct Seal {
+    threshold: u8,
+    address: Address,
+    token: Secret,
+    secret: SealedSecret,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Shares submitted since the key was last sealed.
+    shares: Vec<Share>,
+    /// Addresses of the reconstructed key's signers; empty while sealed.
+    unsealed: Vec<Address>,
+}
+
+/// Progress of unsealing, as reported by the seal endpoints.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SealStatus {
+    pub sealed: bool,
+    pub threshold: u8,
+    /// Shares submitted towards the threshold.
+    pub progress: usize,
+}
+
+impl Seal {
+    pub fn new(config: &SignerConfig) -> Result<Option<Self>> {
+        let SignerConfig::Sealed {
+            threshold,
+            address,
+            token,
+            secret,
+        } = config
+        else {
+            return Ok(None);
+        };
+        if *threshold < 2 {
+            return Err(Error::InvalidConfig(
+                "sealed signer threshold must be at least 2".to_string(),
+            ));
+        }
+        if token.expose().is_empty() {
+            return Err(Error::RequireConfigKeyNotFound("token"));
+        }
+        Ok(Some(Seal {
+            threshold: *threshold,
+            address: *address,
+            token: token.clone(),
+            secret: secret.clone(),
+            state: Mutex::default(),
+        }))
+    }
+
+    /// Whether `token` is the configured bearer token.
+    pub fn authorize(&self, token: &str) -> bool {
+        self.token.matches(token)
+    }
+
+    pub fn status(&self) -> SealStatus {
+        let state = self.state.lock().unwrap();
+        SealStatus {
+            sealed: state.unsealed.is_empty(),
+            threshold: self.threshold,
+            progress: state.shares.len(),
+        }
+    }
+
+    pub fn is_sealed(&self) -> bool {
+        self.state.lock().unwrap().unsealed.is_empty()
+    }
+
+    /// Add a hex encoded share. The share that completes the threshold
+    /// returns the reconstructed key's signers, or an error when the shares
+    /// do not reconstruct the configured key; either way the collected shares
+    /// are discarded.
+    pub fn submit(&self, share: &str) -> Result<Option<Vec<BoxSigner>>> {
+        let bytes = Zeroizing::new(
+            alloy::hex::decode(share.trim())
+                .map_err(|_| Error::InvalidShare("not hex".to_string()))?,
+        );
+        let share = Share::try_from(bytes.as_slice()).map_err(|e| Error::InvalidShare(e.into()))?;
+        let shares = {
+            let mut state = self.state.lock().unwrap();
+            if !state.unsealed.is_empty() {
+                return Err(Error::InvalidShare("already unsealed".to_string()));
+            }
+            if state.shares.iter().any(|s| s.x == share.x) {
+                return Err(Error::InvalidShare(format!(
+                    "share {} was already submitted",
+                    share.x.0
+                )));
+            }
+            if state
+                .shares
+                .first()
+                .is_some_and(|s| s.y.len() != share.y.len())
+            {
+                return Err(Error::InvalidShare(
+                    "length differs from the other shares".to_string(),
+                ));
+            }
+            state.shares.push(share);
+            if state.shares.len() < usize::from(self.threshold) {
+                return Ok(None);
+            }
+            std::mem::take(&mut state.shares)
+        };
+
+        let signers = self.reconstruct(&shares)?;
+        self.state.lock().unwrap().unsealed = signers.iter().map(|s| s.address()).collect();
+        Ok(Some(signers))
+    }
+
+    fn reconstruct(&self, shares: &[Share]) -> Result<Vec<BoxSigner>> {
+        // Parse errors could quote parts of the secret, so none are passed on.
+        let invalid = || Error::InvalidShare(format!("shares do not reconstruct {}", self.address));
+        let bytes = Zeroizing::new(
+            Sharks(self.threshold)
+                .recover(shares)
+                .map_err(|e| Error::InvalidShare(e.into()))?,
+        );
+        let secret = std::str::from_utf8(&bytes).map_err(|_| invalid())?;
+        let config = self.secret.unsealed(Secret::new(secret.trim().to_string()));
+        let signers = config.local_signers().map_err(|_| invalid())?;
+        match signers.first() {
+            Some(signer) if signer.address() == self.address => Ok(signers),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Forget the key and any submitted shares, returning the addresses of
+    /// the key's signers.
+    pub fn seal(&self) -> Vec<Address> {
+        let mut state = self.state.lock().unwrap();
+        state.shares.clear();
+        std::mem::take(&mut state.unsealed)
+    }
+}
+
+/// Split `secret` into `count` hex encoded shares, any `threshold` of which
+/// reconstruct it.
+pub fn split_secret(secret: &str, threshold: u8, count: u8) -> Result<Vec<String>> {
+    if threshold < 2 || count < threshold {
+        return Err(Error::InvalidConfig(
+            "need at least 2 shares to reconstruct and no fewer shares than that".to_string(),
+        ));
+    }
+    Ok(Sharks(threshold)
+        .dealer(secret.trim().as_bytes())
+        .take(usize::from(count))
+        .map(|share| {
+            let bytes = Zeroizing::new(Vec::from(&share));
+            alloy::hex::encode(&*bytes)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d2e3f4a5b6c7d8e9f0a1b2";
+
+    fn seal(threshold: u8) -> Seal {
+        let config: SignerConfig = serde_json::from_value(serde_json::json!({
+            "type": "sealed",
+            "threshold": threshold,
+            "address": "0xbb48b4d059D901F0CE1325d1A37f9E14C6634499",
+            "token": "operators",
+        }))
+        .unwrap();
+        Seal::new(&config).unwrap().unwrap()
+    }
+
+    #[test]
+    fn unseal_with_threshold_shares() {
+        let shares = split_secret(KEY, 2, 3).unwrap();
+        let seal = seal(2);
+        assert!(seal.authorize("operators"));
+        assert!(!seal.authorize("operator"));
+
+        assert!(seal.submit(&shares[2]).unwrap().is_none());
+        assert!(seal.submit(&shares[2]).is_err());
+        let signers = seal.submit(&shares[0]).unwrap().unwrap();
+        assert_eq!(signers[0].address(), seal.address);
+        assert!(!seal.is_sealed());
+
+        assert_eq!(seal.seal(), vec![seal.address]);
+        assert!(seal.is_sealed());
+    }
+
+    #[test]
+    fn recover_from_exactly_threshold_shares() {
+        let shares: Vec<Share> = split_secret(KEY, 3, 5)
+            .unwrap()
+            .iter()
+            .map(|share| Share::try_from(alloy::hex::decode(share).unwrap().as_slice()).unwrap())
+            .collect();
+        let recovered = Sharks(3).recover(&shares[1..4]).unwrap();
+        assert_eq!(recovered, KEY.as_bytes());
+        assert!(Sharks(3).recover(&shares[..2]).is_err());
+    }
+
+    #[test]
+    fn discard_shares_of_another_key() {
+        let other = split_secret(&KEY.replace('a', "b"), 2, 2).unwrap();
+        let seal = seal(2);
+        seal.submit(&other[0]).unwrap();
+        assert!(seal.submit(&other[1]).is_err());
+        assert_eq!(seal.status().progress, 0);
+        assert!(seal.is_sealed());
+    }
+}
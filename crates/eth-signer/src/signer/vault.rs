@@ -0,0 +1,609 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    consensus::{SignableTransaction, TxEnvelope, constants::LEGACY_TX_TYPE_ID},
+    eips::eip2718::Decodable2718,
+    network::TxSigner,
+    primitives::{Address, B256, Signature, TxKind},
+    signers::local::PrivateKeySigner,
+    transports::http::reqwest::{self, Method},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, de::DeserializeOwned, de::IgnoredAny};
+use serde_json::json;
+use tokio::sync::Mutex;
+use zeroize::Zeroizing;
+
+use super::{KeySigner, recover_signature};
+use crate::prelude::*;
+use crate::secret::{self, Secret};
+
+/// Connection to a HashiCorp Vault or OpenBao server.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct VaultConfig {
+    /// Server address, e.g. `https://vault.example.com:8200`.
+    pub address: String,
+    /// Namespace sent as `X-Vault-Namespace`.
+    #[serde(default)]
+    pub namespace: Option<String>,
+    pub auth: VaultAuth,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(tag = "method", rename_all = "snake_case", deny_unknown_fields)]
+pub enum VaultAuth {
+    Token {
+        /// Vault token, or `file:<path>`.
+        token: Secret,
+    },
+    #[serde(rename = "approle", alias = "app_role")]
+    AppRole {
+        role_id: String,
+        /// AppRole secret ID, or `file:<path>`.
+        secret_id: Secret,
+        #[serde(default = "default_approle_mount")]
+        mount: String,
+    },
+    Kubernetes {
+        role: String,
+        /// Service account token presented to Vault, read again at every login.
+        #[serde(default = "default_jwt_path")]
+        jwt_path: String,
+        #[serde(default = "default_kubernetes_mount")]
+        mount: String,
+    },
+}
+
+fn default_approle_mount() -> String {
+    "approle".to_string()
+}
+
+fn default_kubernetes_mount() -> String {
+    "kubernetes".to_string()
+}
+
+fn default_jwt_path() -> String {
+    "/var/run/secrets/kubernetes.io/serviceaccount/token".to_string()
+}
+
+impl VaultConfig {
+    pub fn secrets_mut(&mut self) -> Vec<&mut Secret> {
+        match &mut self.auth {
+            VaultAuth::Token { token } => vec![token],
+            VaultAuth::AppRole { secret_id, .. } => vec![secret_id],
+            VaultAuth::Kubernetes { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Authenticated Vault client. The token is renewed when two thirds of its
+/// lease have passed, and a new one is requested when renewal fails.
+pub struct VaultClient {
+    http: reqwest::Client,
+    address: String,
+    namespace: Option<String>,
+    auth: VaultAuth,
+    token: Mutex<Option<Token>>,
+}
+
+struct Token {
+    value: Secret,
+    renewable: bool,
+    issued: Instant,
+    /// Zero for tokens that never expire, such as root tokens.
+    lease: Duration,
+}
+
+impl Token {
+    fn stale(&self) -> bool {
+        !self.lease.is_zero() && self.issued.elapsed() > self.lease * 2 / 3
+    }
+}
+
+#[derive(Deserialize)]
+struct AuthResponse {
+    auth: AuthData,
+}
+
+#[derive(Deserialize)]
+struct AuthData {
+    client_token: Secret,
+    lease_duration: u64,
+    renewable: bool,
+}
+
+#[derive(Deserialize)]
+struct LookupResponse {
+    data: LookupData,
+}
+
+#[derive(Deserialize)]
+struct LookupData {
+    ttl: u64,
+    renewable: bool,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    errors: Vec<String>,
+}
+
+impl VaultClient {
+    pub fn new(config: &VaultConfig) -> Result<Self> {
+        Ok(VaultClient {
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()?,
+            address: config.address.trim_end_matches('/').to_string(),
+            namespace: config.namespace.clone(),
+            auth: config.auth.clone(),
+            token: Mutex::new(None),
+        })
+    }
+
+    /// Call `/v1/<path>` with a valid token.
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<T> {
+        let token = self.token().await?;
+        self.send(method, path, Some(&token), body).await
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        token: Option<&Secret>,
+        body: Option<serde_json::Value>,
+    ) -> Result<T> {
+        let mut builder = self
+            .http
+            .request(method, format!("{}/v1/{}", self.address, path));
+        if let Some(token) = token {
+            builder = builder.header("X-Vault-Token", token.expose());
+        }
+        if let Some(namespace) = &self.namespace {
+            builder = builder.header("X-Vault-Namespace", namespace);
+        }
+        if let Some(body) = body {
+            builder = builder.json(&body);
+        }
+        for (name, value) in crate::otel::current_context_headers() {
+            builder = builder.header(name, value);
+        }
+        let response = builder.send().await?;
+        let status = response.status();
+        // Responses may carry key material.
+        let bytes = Zeroizing::new(response.bytes().await?.to_vec());
+        if !status.is_success() {
+            let errors = serde_json::from_slice::<ErrorResponse>(&bytes)
+                .map(|e| e.errors.join("; "))
+                .unwrap_or_default();
+            return Err(Error::Vault(format!("{path}: {status} {errors}")));
+        }
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    async fn token(&self) -> Result<Secret> {
+        let mut token = self.token.lock().await;
+        match token.take() {
+            Some(current) if !current.stale() => {
+                let value = current.value.clone();
+                *token = Some(current);
+                return Ok(value);
+            }
+            Some(current) if current.renewable => match self.renew(&current.value).await {
+                Ok(renewed) => *token = Some(renewed),
+                Err(e) => {
+                    tracing::warn!("renew vault token: {}, logging in again", e);
+                    *token = Some(self.login().await?);
+                }
+            },
+            _ => *token = Some(self.login().await?),
+        }
+        Ok(token.as_ref().unwrap().value.clone())
+    }
+
+    async fn login(&self) -> Result<Token> {
+        let (mount, body) = match &self.auth {
+            VaultAuth::Token { token } => {
+                let lookup: LookupResponse = self
+                    .send(Method::GET, "auth/token/lookup-self", Some(token), None)
+                    .await?;
+                return Ok(Token {
+                    value: token.clone(),
+                    renewable: lookup.data.renewable,
+                    issued: Instant::now(),
+                    lease: Duration::from_secs(lookup.data.ttl),
+                });
+            }
+            VaultAuth::AppRole {
+                role_id,
+                secret_id,
+                mount,
+            } => (
+                mount,
+                json!({"role_id": role_id, "secret_id": secret_id.expose()}),
+            ),
+            VaultAuth::Kubernetes {
+                role,
+                jwt_path,
+                mount,
+            } => {
+                // Projected service account tokens are world-readable by design.
+                let jwt = secret::read_shared_file(jwt_path)?;
+                (mount, json!({"role": role, "jwt": jwt.expose()}))
+            }
+        };
+        let response: AuthResponse = self
+            .send(
+                Method::POST,
+                &format!("auth/{mount}/login"),
+                None,
+                Some(body),
+            )
+            .await?;
+        Ok(response.auth.into())
+    }
+
+    async fn renew(&self, token: &Secret) -> Result<Token> {
+        let response: AuthResponse = self
+            .send(
+                Method::POST,
+                "auth/token/renew-self",
+                Some(token),
+                Some(json!({})),
+            )
+            .await?;
+        Ok(response.auth.into())
+    }
+}
+
+impl From<AuthData> for Token {
+    fn from(auth: AuthData) -> Self {
+        Token {
+            value: auth.client_token,
+            renewable: auth.renewable,
+            issued: Instant::now(),
+            lease: Duration::from_secs(auth.lease_duration),
+        }
+    }
+}
+
+/// Private key stored in a KV version 2 secret engine. The secret is read
+/// again when its version changes, so a rotated key replaces the old one.
+pub struct VaultKv {
+    client: Arc<VaultClient>,
+    mount: String,
+    path: String,
+    field: String,
+    version: u64,
+}
+
+#[derive(Deserialize)]
+struct KvResponse {
+    data: KvData,
+}
+
+#[derive(Deserialize)]
+struct KvData {
+    data: HashMap<String, KvValue>,
+    metadata: KvMetadata,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KvValue {
+    Secret(Secret),
+    Other(IgnoredAny),
+}
+
+#[derive(Deserialize)]
+struct KvMetadata {
+    version: u64,
+}
+
+impl VaultKv {
+    pub fn new(client: Arc<VaultClient>, mount: &str, path: &str, field: &str) -> Self {
+        VaultKv {
+            client,
+            mount: mount.trim_matches('/').to_string(),
+            path: path.trim_matches('/').to_string(),
+            field: field.to_string(),
+            version: 0,
+        }
+    }
+
+    /// `<mount>/<path>`, for logs.
+    pub fn location(&self) -> String {
+        format!("{}/{}", self.mount, self.path)
+    }
+
+    /// Read the key if the secret version changed since the last read.
+    pub async fn refresh(&mut self) -> Result<Option<PrivateKeySigner>> {
+        let response: KvResponse = self
+            .client
+            .request(
+                Method::GET,
+                &format!("{}/data/{}", self.mount, self.path),
+                None,
+            )
+            .await?;
+        if response.data.metadata.version == self.version {
+            return Ok(None);
+        }
+        let Some(KvValue::Secret(key)) = response.data.data.get(&self.field) else {
+            return Err(Error::Vault(format!(
+                "{} has no string field '{}'",
+                self.location(),
+                self.field
+            )));
+        };
+        let signer = key.expose().parse::<PrivateKeySigner>()?;
+        self.version = response.data.metadata.version;
+        Ok(Some(signer))
+    }
+}
+
+/// Account of the `vault-ethereum` plugin (immutability-io, v0.3) mounted at
+/// `mount`; the key never leaves Vault. The plugin builds and signs legacy
+/// EIP-155 transactions from their fields:
+///
+/// - `GET <mount>/accounts/<name>` answers `{"data": {"address": ...}}`;
+/// - `POST <mount>/accounts/<name>/sign-tx` takes `to`, `amount`, `nonce`,
+///   `gas_limit`, `gas_price` and `chainID` as decimal strings and `data` as
+///   hex, and answers `{"data": {"signed_transaction": "0x..."}}`.
+///
+/// The returned signature must recover to the account over the transaction
+/// that was requested, so a plugin that signs different fields is refused.
+pub struct VaultPluginSigner {
+    client: Arc<VaultClient>,
+    path: String,
+    address: Address,
+}
+
+#[derive(Deserialize)]
+struct PluginResponse<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct PluginAccount {
+    address: Address,
+}
+
+#[derive(Deserialize)]
+struct PluginSignedTx {
+    signed_transaction: String,
+}
+
+impl VaultPluginSigner {
+    pub async fn new(client: Arc<VaultClient>, mount: &str, name: &str) -> Result<Self> {
+        let path = format!("{}/accounts/{}", mount.trim_matches('/'), name);
+        let address = Self::account(&client, &path).await?;
+        Ok(VaultPluginSigner {
+            client,
+            path,
+            address,
+        })
+    }
+
+    async fn account(client: &VaultClient, path: &str) -> Result<Address> {
+        let response: PluginResponse<PluginAccount> =
+            client.request(Method::GET, path, None).await?;
+        Ok(response.data.address)
+    }
+
+    async fn sign_tx(&self, tx: &dyn SignableTransaction<Signature>) -> Result<Signature> {
+        if tx.ty() != LEGACY_TX_TYPE_ID {
+            return Err(Error::Vault(
+                "vault-ethereum only signs legacy transactions".to_string(),
+            ));
+        }
+        let TxKind::Call(to) = tx.kind() else {
+            return Err(Error::Vault(
+                "vault-ethereum cannot sign contract creations".to_string(),
+            ));
+        };
+        let chain_id = tx
+            .chain_id()
+            .ok_or_else(|| Error::Vault("vault-ethereum needs a chain id".to_string()))?;
+        let body = json!({
+            "to": to,
+            "amount": tx.value().to_string(),
+            "nonce": tx.nonce().to_string(),
+            "gas_limit": tx.gas_limit().to_string(),
+            "gas_price": tx.gas_price().unwrap_or_default().to_string(),
+            "data": tx.input(),
+            "chainID": chain_id.to_string(),
+        });
+        let response: PluginResponse<PluginSignedTx> = self
+            .client
+            .request(Method::POST, &format!("{}/sign-tx", self.path), Some(body))
+            .await?;
+        let raw = alloy::hex::decode(&response.data.signed_transaction)?;
+        let signed = TxEnvelope::decode_2718(&mut raw.as_slice())?;
+        recover_signature(
+            &signed.signature().as_bytes(),
+            &tx.signature_hash(),
+            self.address,
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl TxSigner<Signature> for VaultPluginSigner {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> alloy::signers::Result<Signature> {
+        self.sign_tx(tx).await.map_err(alloy::signers::Error::other)
+    }
+}
+
+#[async_trait::async_trait]
+impl KeySigner for VaultPluginSigner {
+    /// Reads the account, which needs a valid token and the key in place.
+    async fn check(&self) -> Result<()> {
+        let address = Self::account(&self.client, &self.path).await?;
+        if address != self.address {
+            return Err(Error::Vault(format!(
+                "account {} now has address {address}",
+                self.path
+            )));
+        }
+        Ok(())
+    }
+
+    async fn sign_digest(&self, _hash: &B256) -> Result<Signature> {
+        Err(Error::Vault(
+            "vault-ethereum only signs transactions".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::{
+        consensus::{TxEip1559, TxLegacy},
+        eips::eip2718::Encodable2718,
+        network::TxSignerSync,
+        primitives::{Bytes, U256},
+    };
+    use axum::{
+        Json, Router,
+        routing::{get, post},
+    };
+
+    const KEY: &str = "0xa1b2c3d4e5f6a1b2c3d4e5f6a1b2c3d4e5f6a1b2c3d4e5f6a1b2c3d4e5f6a1b2";
+
+    #[tokio::test]
+    async fn read_kv_secret() {
+        let app = Router::new()
+            .route(
+                "/v1/auth/token/lookup-self",
+                get(|| async { Json(json!({"data": {"ttl": 0, "renewable": false}})) }),
+            )
+            .route(
+                "/v1/secret/data/eth/hot",
+                get(|| async {
+                    Json(json!({"data": {
+                        "data": {"private_key": KEY, "note": 1},
+                        "metadata": {"version": 3}
+                    }}))
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let config = VaultConfig {
+            address: format!("http://{address}"),
+            namespace: None,
+            auth: VaultAuth::Token {
+                token: Secret::new("root".to_string()),
+            },
+        };
+        let client = Arc::new(VaultClient::new(&config).unwrap());
+        let mut kv = VaultKv::new(client, "secret", "eth/hot", "private_key");
+        let signer = kv.refresh().await.unwrap().expect("first read");
+        assert_eq!(
+            signer.address(),
+            KEY.parse::<PrivateKeySigner>().unwrap().address()
+        );
+        assert!(kv.refresh().await.unwrap().is_none(), "same version");
+    }
+
+    /// Stand-in for the `sign-tx` endpoint of vault-ethereum, signing the
+    /// transaction described by the request fields with `KEY`.
+    async fn sign_tx(Json(body): Json<serde_json::Value>) -> Json<serde_json::Value> {
+        let field = |name: &str| body[name].as_str().unwrap().to_string();
+        let mut tx = TxLegacy {
+            chain_id: Some(field("chainID").parse().unwrap()),
+            nonce: field("nonce").parse().unwrap(),
+            gas_price: field("gas_price").parse().unwrap(),
+            gas_limit: field("gas_limit").parse().unwrap(),
+            to: TxKind::Call(field("to").parse().unwrap()),
+            value: field("amount").parse().unwrap(),
+            input: field("data").parse().unwrap(),
+        };
+        let signer = KEY.parse::<PrivateKeySigner>().unwrap();
+        let signature = signer.sign_transaction_sync(&mut tx).unwrap();
+        let signed = TxEnvelope::from(tx.into_signed(signature));
+        Json(json!({"data": {
+            "signed_transaction": alloy::hex::encode_prefixed(signed.encoded_2718()),
+            "transaction_hash": signed.tx_hash(),
+        }}))
+    }
+
+    #[tokio::test]
+    async fn sign_with_plugin() {
+        let address = KEY.parse::<PrivateKeySigner>().unwrap().address();
+        let app = Router::new()
+            .route(
+                "/v1/auth/token/lookup-self",
+                get(|| async { Json(json!({"data": {"ttl": 0, "renewable": false}})) }),
+            )
+            .route(
+                "/v1/ethereum/accounts/hot",
+                get(move || async move { Json(json!({"data": {"address": address}})) }),
+            )
+            .route("/v1/ethereum/accounts/hot/sign-tx", post(sign_tx));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let config = VaultConfig {
+            address: format!("http://{server}"),
+            namespace: None,
+            auth: VaultAuth::Token {
+                token: Secret::new("root".to_string()),
+            },
+        };
+        let client = Arc::new(VaultClient::new(&config).unwrap());
+        let signer = VaultPluginSigner::new(client, "ethereum", "hot")
+            .await
+            .unwrap();
+        assert_eq!(signer.address(), address);
+        signer.check().await.unwrap();
+
+        let mut tx = TxLegacy {
+            chain_id: Some(1),
+            nonce: 7,
+            gas_price: 2_000_000_000,
+            gas_limit: 21_000,
+            to: TxKind::Call(Address::repeat_byte(2)),
+            value: U256::from(10),
+            input: Bytes::from_static(b"call"),
+        };
+        let signature = signer.sign_transaction(&mut tx).await.unwrap();
+        assert_eq!(
+            signature
+                .recover_address_from_prehash(&tx.signature_hash())
+                .unwrap(),
+            address
+        );
+
+        let mut eip1559 = TxEip1559 {
+            chain_id: 1,
+            to: TxKind::Call(Address::repeat_byte(2)),
+            ..Default::default()
+        };
+        assert!(signer.sign_transaction(&mut eip1559).await.is_err());
+        // The plugin has no endpoint for hashes, so authorizations are refused.
+        assert!(signer.sign_digest(&B256::ZERO).await.is_err());
+    }
+}
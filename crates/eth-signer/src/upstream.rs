@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use alloy::{
+    rpc::json_rpc::{Request as JrpcRequest, Response as JrpcResponse},
+    transports::http::reqwest,
+};
+
+use crate::config::UpstreamConfig;
+use crate::prelude::*;
+use crate::route::Params;
+
+/// JSON-RPC node that answers the methods the signer does not serve. The
+/// signer never talks to it on its own; requests are only passed through.
+pub struct Upstream {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Upstream {
+    pub fn new(config: &UpstreamConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?;
+        Ok(Self {
+            client,
+            url: config.url.clone(),
+        })
+    }
+
+    pub async fn forward(&self, request: &JrpcRequest<Params>) -> Result<JrpcResponse> {
+        let mut builder = self.client.post(&self.url).json(request);
+        for (name, value) in crate::otel::current_context_headers() {
+            builder = builder.header(name, value);
+        }
+        Ok(builder.send().await?.error_for_status()?.json().await?)
+    }
+}
@@ -0,0 +1,191 @@
+use alloy::{
+    consensus::{Transaction, TxEnvelope, transaction::SignerRecoverable},
+    eips::{
+        eip2718::{Decodable2718, Encodable2718},
+        eip7702::{Authorization, SignedAuthorization},
+    },
+    primitives::{Address, TxKind},
+    rpc::types::TransactionRequest,
+};
+
+use crate::prelude::*;
+
+/// Check a signed transaction before it is released. Its encoding must
+/// decode to the same transaction, the signature must recover to the
+/// request's `from`, and every field the request sets must be signed as
+/// given. A failure means the backend signed with another key or signed
+/// something else, such as a misconfigured KMS key version.
+pub fn check_signed(request: &TransactionRequest, envelope: &TxEnvelope) -> Result<()> {
+    let decoded = TxEnvelope::decode_2718(&mut envelope.encoded_2718().as_slice())
+        .map_err(|e| Error::SelfCheck(format!("encoding does not decode: {e}")))?;
+    if decoded != *envelope {
+        return Err(Error::SelfCheck(
+            "encoding decodes to another transaction".to_string(),
+        ));
+    }
+
+    let signer = decoded
+        .recover_signer()
+        .map_err(|e| Error::SelfCheck(format!("signature does not recover: {e}")))?;
+    if let Some(from) = request.from.filter(|from| *from != signer) {
+        return Err(Error::SelfCheck(format!(
+            "signed by {signer} instead of {from}"
+        )));
+    }
+
+    let mut differs = Vec::new();
+    let mut compare = |field: &'static str, same: bool| {
+        if !same {
+            differs.push(field);
+        }
+    };
+    compare(
+        "type",
+        request
+            .transaction_type
+            .is_none_or(|ty| ty == u8::from(decoded.tx_type())),
+    );
+    compare(
+        "chainId",
+        request
+            .chain_id
+            .is_none_or(|id| decoded.chain_id() == Some(id)),
+    );
+    compare(
+        "nonce",
+        request.nonce.is_none_or(|nonce| nonce == decoded.nonce()),
+    );
+    compare("to", request.to.unwrap_or(TxKind::Create) == decoded.kind());
+    compare(
+        "value",
+        request.value.unwrap_or_default() == decoded.value(),
+    );
+    compare(
+        "input",
+        request.input.input().map_or(&[][..], |input| input) == &decoded.input()[..],
+    );
+    compare(
+        "gas",
+        request.gas.is_none_or(|gas| gas == decoded.gas_limit()),
+    );
+    compare(
+        "gasPrice",
+        request
+            .gas_price
+            .is_none_or(|price| decoded.gas_price() == Some(price)),
+    );
+    compare(
+        "maxFeePerGas",
+        request
+            .max_fee_per_gas
+            .is_none_or(|fee| fee == decoded.max_fee_per_gas()),
+    );
+    compare(
+        "maxPriorityFeePerGas",
+        request
+            .max_priority_fee_per_gas
+            .is_none_or(|fee| decoded.max_priority_fee_per_gas() == Some(fee)),
+    );
+    compare(
+        "maxFeePerBlobGas",
+        request
+            .max_fee_per_blob_gas
+            .is_none_or(|fee| decoded.max_fee_per_blob_gas() == Some(fee)),
+    );
+    // Lists the request leaves out must be signed empty.
+    compare(
+        "accessList",
+        non_empty(request.access_list.as_ref().map(|l| &l[..]))
+            == non_empty(decoded.access_list().map(|l| &l[..])),
+    );
+    compare(
+        "blobVersionedHashes",
+        non_empty(request.blob_versioned_hashes.as_deref())
+            == non_empty(decoded.blob_versioned_hashes()),
+    );
+    compare(
+        "authorizationList",
+        non_empty(request.authorization_list.as_deref()) == non_empty(decoded.authorization_list()),
+    );
+    // A blob transaction is returned with its sidecar, which the signature
+    // does not cover.
+    compare(
+        "sidecar",
+        request.sidecar.as_ref() == decoded.as_eip4844().and_then(|tx| tx.tx().sidecar()),
+    );
+    if !differs.is_empty() {
+        return Err(Error::SelfCheck(format!(
+            "signed transaction differs from the request in {}",
+            differs.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Check a signed EIP-7702 authorization before it is released: it must be
+/// the requested authorization, and its signature must recover to `from`.
+pub fn check_authorization(
+    from: Address,
+    requested: &Authorization,
+    signed: &SignedAuthorization,
+) -> Result<()> {
+    if signed.inner() != requested {
+        return Err(Error::SelfCheck(
+            "signed authorization differs from the request".to_string(),
+        ));
+    }
+    let authority = signed
+        .recover_authority()
+        .map_err(|e| Error::SelfCheck(format!("signature does not recover: {e}")))?;
+    if authority != from {
+        return Err(Error::SelfCheck(format!(
+            "signed by {authority} instead of {from}"
+        )));
+    }
+    Ok(())
+}
+
+fn non_empty<T>(list: Option<&[T]>) -> Option<&[T]> {
+    list.filter(|list| !list.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::{
+        network::{EthereumWallet, TransactionBuilder},
+        primitives::{Address, U256},
+        signers::local::PrivateKeySigner,
+    };
+
+    fn request(from: Address) -> TransactionRequest {
+        TransactionRequest::default()
+            .with_from(from)
+            .with_to(Address::repeat_byte(2))
+            .with_value(U256::from(1))
+            .with_nonce(7)
+            .with_chain_id(1)
+            .with_gas_limit(21_000)
+            .with_max_fee_per_gas(2)
+            .with_max_priority_fee_per_gas(1)
+    }
+
+    #[tokio::test]
+    async fn refuse_mismatched_signatures() {
+        let signer = PrivateKeySigner::random();
+        let request = request(signer.address());
+        let envelope = request
+            .clone()
+            .build(&EthereumWallet::new(signer))
+            .await
+            .unwrap();
+        check_signed(&request, &envelope).unwrap();
+
+        let other = request.clone().with_from(Address::repeat_byte(3));
+        assert!(check_signed(&other, &envelope).is_err());
+
+        let other = request.clone().with_nonce(8).with_value(U256::from(2));
+        let error = check_signed(&other, &envelope).unwrap_err().to_string();
+        assert!(error.ends_with("in nonce, value"), "{error}");
+    }
+}